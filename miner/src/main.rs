@@ -0,0 +1,146 @@
+//! A standalone CLE Coin miner. It fetches work from a mining node over RPC,
+//! hashes on a number of threads and submits the seals it finds. The node
+//! builds and imports the blocks, so the miner needs no chain database.
+
+#![warn(unused_extern_crates)]
+
+mod rpc;
+
+use std::sync::{Arc, RwLock, atomic::{AtomicU64, Ordering}};
+use std::thread;
+use std::time::{Duration, Instant};
+use log::{info, warn};
+use rand::{thread_rng, Rng};
+use structopt::StructOpt;
+use sp_core::{U256, crypto::{Ss58AddressFormat, set_default_ss58_version}};
+use pow::{Work, SS58_PREFIX};
+
+/// Number of nonces a thread tries before checking whether its work is stale.
+const BATCH_SIZE: u32 = 1024;
+
+/// How often the hashrate is reported.
+const REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "cle-miner", about = "Standalone miner for CLE Coin")]
+struct Args {
+	/// HTTP RPC endpoint of a mining node.
+	#[structopt(long, default_value = "http://localhost:9933")]
+	rpc_url: String,
+
+	/// Number of threads to mine with.
+	#[structopt(long, default_value = "1")]
+	threads: usize,
+
+	/// How often to ask the node for new work, in milliseconds.
+	#[structopt(long, default_value = "500")]
+	poll_interval: u64,
+}
+
+/// The work being mined, tagged with a generation that changes whenever the
+/// work does so that mining threads notice when theirs has gone stale.
+#[derive(Default)]
+struct SharedWork {
+	current: RwLock<(u64, Option<Work>)>,
+}
+
+impl SharedWork {
+	fn get(&self) -> (u64, Option<Work>) {
+		self.current.read().expect("Mining threads never panic while holding the lock; qed").clone()
+	}
+
+	fn generation(&self) -> u64 {
+		self.current.read().expect("Mining threads never panic while holding the lock; qed").0
+	}
+
+	/// Replace the work if it changed. Returns whether it did.
+	fn update(&self, work: Option<Work>) -> bool {
+		let mut current = self.current.write().expect("Mining threads never panic while holding the lock; qed");
+		if current.1 == work {
+			return false
+		}
+		*current = (current.0 + 1, work);
+		true
+	}
+}
+
+/// Mine whatever work is current, forever. Each thread starts every piece of
+/// work from its own random nonce.
+fn mine(client: rpc::Client, shared: Arc<SharedWork>, hashes: Arc<AtomicU64>, idle: Duration) {
+	let mut rng = thread_rng();
+
+	loop {
+		let (generation, work) = shared.get();
+		let work = match work {
+			Some(work) => work,
+			None => {
+				thread::sleep(idle);
+				continue
+			}
+		};
+
+		let mut nonce = U256::from(&rng.gen::<[u8; 32]>()[..]);
+		while shared.generation() == generation {
+			let (seal, tried) = work.mine_range(nonce, BATCH_SIZE);
+			hashes.fetch_add(tried.into(), Ordering::Relaxed);
+
+			if let Some(seal) = seal {
+				match client.submit_work(work.pre_hash, &seal) {
+					Ok(true) => info!("Found a seal for {}", work.pre_hash),
+					Ok(false) => warn!("Node rejected our seal for {}", work.pre_hash),
+					Err(e) => warn!("Submitting seal failed: {}", e),
+				}
+			}
+
+			nonce = nonce.overflowing_add(tried.into()).0;
+		}
+	}
+}
+
+fn main() {
+	env_logger::from_env(env_logger::Env::default().default_filter_or("info")).init();
+	// The node sends the work's author with CLE Coin's address prefix
+	set_default_ss58_version(Ss58AddressFormat::Custom(SS58_PREFIX));
+
+	let args = Args::from_args();
+	let client = rpc::Client::new(args.rpc_url.clone());
+	let shared = Arc::new(SharedWork::default());
+	let hashes = Arc::new(AtomicU64::new(0));
+	let poll_interval = Duration::from_millis(args.poll_interval);
+
+	info!("Mining against {} on {} threads", args.rpc_url, args.threads);
+
+	for index in 0..args.threads {
+		let (client, shared, hashes) = (client.clone(), shared.clone(), hashes.clone());
+		thread::Builder::new()
+			.name(format!("cle-miner-{}", index))
+			.spawn(move || mine(client, shared, hashes, poll_interval))
+			.expect("Spawning mining thread failed");
+	}
+
+	let mut last_report = Instant::now();
+	let mut last_hashes = 0;
+	loop {
+		match client.get_work() {
+			Ok(work) => if shared.update(work.clone()) {
+				match work {
+					Some(work) => info!(
+						"New {:?} work {} at difficulty {}", work.algorithm, work.pre_hash, work.difficulty,
+					),
+					None => info!("Node has no work; waiting"),
+				}
+			},
+			Err(e) => warn!("Fetching work failed: {}", e),
+		}
+
+		if last_report.elapsed() >= REPORT_INTERVAL {
+			let total = hashes.load(Ordering::Relaxed);
+			let millis = last_report.elapsed().as_millis().max(1) as u64;
+			info!("⛏  Mining at {} H/s", (total - last_hashes).saturating_mul(1000) / millis);
+			last_report = Instant::now();
+			last_hashes = total;
+		}
+
+		thread::sleep(poll_interval);
+	}
+}
@@ -0,0 +1,50 @@
+//! A minimal client for the node's remote mining RPCs.
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use codec::Encode;
+use sp_core::{Bytes, H256};
+use pow::{Seal, Work};
+
+/// Talks to a mining node over HTTP JSON-RPC.
+#[derive(Clone)]
+pub struct Client {
+	url: String,
+}
+
+impl Client {
+	pub fn new(url: String) -> Self {
+		Self { url }
+	}
+
+	/// The work the node is currently mining, if any.
+	pub fn get_work(&self) -> Result<Option<Work>, String> {
+		self.call("mining_getWork", json!([]))
+	}
+
+	/// Submit a seal for the work identified by `pre_hash`. Returns whether the node accepted it.
+	pub fn submit_work(&self, pre_hash: H256, seal: &Seal) -> Result<bool, String> {
+		self.call("mining_submitWork", json!([pre_hash, Bytes(seal.encode())]))
+	}
+
+	fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, String> {
+		let response = ureq::post(&self.url).send_json(json!({
+			"jsonrpc": "2.0",
+			"id": 1,
+			"method": method,
+			"params": params,
+		}));
+		if !response.ok() {
+			return Err(format!("{} failed: {}", method, response.status_line()))
+		}
+
+		let mut reply: Value = response.into_json()
+			.map_err(|e| format!("{} returned invalid JSON: {:?}", method, e))?;
+		if let Some(error) = reply.get("error") {
+			return Err(format!("{} failed: {}", method, error))
+		}
+
+		serde_json::from_value(reply["result"].take())
+			.map_err(|e| format!("{} returned an unexpected result: {:?}", method, e))
+	}
+}
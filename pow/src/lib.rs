@@ -0,0 +1,438 @@
+//! Proof of Work primitives shared by the CLE Coin node, runtime and standalone miner.
+//!
+//! Without the `std` feature only the types are available, so that the runtime
+//! can store them. Hashing requires `std`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use sp_core::{U256, H256, crypto::AccountId32};
+use codec::{Encode, Decode, Input, Output};
+#[cfg(feature = "std")]
+use sp_core::hashing::blake2_256;
+#[cfg(feature = "std")]
+use sha3::{Sha3_256, Digest};
+#[cfg(feature = "std")]
+use tiny_keccak::{Hasher, Keccak};
+#[cfg(feature = "std")]
+use serde::{Serialize, Deserialize};
+
+/// Salt for the Argon2 algorithm. Argon2 requires one, but PoW gains nothing from a secret salt.
+#[cfg(feature = "std")]
+const ARGON2_SALT: &[u8] = b"cle-coin-pow";
+
+/// The hash functions a chain can use for Proof of Work.
+#[derive(Clone, Copy, PartialEq, Eq, Encode, Decode, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "lowercase"))]
+pub enum Algorithm {
+	/// Sha3-256. The algorithm CLE Coin launched with.
+	Sha3,
+	/// Blake2b with a 256 bit output.
+	Blake2b,
+	/// Keccak-256, as used by Ethereum.
+	Keccak,
+	/// Argon2id with 4 MiB of memory. A memory-hard option that narrows the gap
+	/// between CPUs and specialised hardware.
+	Argon2,
+}
+
+impl Default for Algorithm {
+	fn default() -> Self {
+		Algorithm::Sha3
+	}
+}
+
+#[cfg(feature = "std")]
+impl Algorithm {
+	/// Hash `data` with this algorithm.
+	pub fn hash(&self, data: &[u8]) -> H256 {
+		match self {
+			Algorithm::Sha3 => H256::from_slice(Sha3_256::digest(data).as_slice()),
+			Algorithm::Blake2b => H256::from(blake2_256(data)),
+			Algorithm::Keccak => {
+				let mut output = [0u8; 32];
+				let mut keccak = Keccak::v256();
+				keccak.update(data);
+				keccak.finalize(&mut output);
+				H256::from(output)
+			}
+			Algorithm::Argon2 => {
+				let config = argon2::Config {
+					variant: argon2::Variant::Argon2id,
+					mem_cost: 4096,
+					time_cost: 1,
+					lanes: 1,
+					thread_mode: argon2::ThreadMode::Sequential,
+					hash_length: 32,
+					..Default::default()
+				};
+				let output = argon2::hash_raw(data, ARGON2_SALT, &config)
+					.expect("The salt and config are valid constants; qed");
+				H256::from_slice(&output)
+			}
+		}
+	}
+}
+
+/// Determine whether the given hash satisfies the given difficulty.
+/// The test is done by multiplying the two together. If the product
+/// overflows the bounds of U256, then the product (and thus the hash)
+/// was too high.
+pub fn hash_meets_difficulty(hash: &H256, difficulty: U256) -> bool {
+	let num_hash = U256::from(&hash[..]);
+	let (_, overflowed) = num_hash.overflowing_mul(difficulty);
+
+	!overflowed
+}
+
+/// `value` as big endian bytes, the form nonces take in seals.
+pub fn u256_to_h256(value: U256) -> H256 {
+	let mut bytes = [0u8; 32];
+	value.to_big_endian(&mut bytes);
+	H256::from(bytes)
+}
+
+/// The account that mines a block, and is rewarded for it.
+pub type AccountId = AccountId32;
+
+/// SS58 address prefix of CLE Coin accounts.
+///
+/// Prefixes from 64 up are encoded in two bytes, so it must be below 64. 61 is
+/// not taken by any network in the SS58 registry.
+pub const SS58_PREFIX: u8 = 61;
+
+/// A change to the PoW parameters that a miner signals in the blocks it mines.
+#[derive(Clone, Copy, PartialEq, Eq, Encode, Decode, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+pub enum Vote {
+	IncreaseReward,
+	DecreaseReward,
+}
+
+/// Votes trail the other fields of seals and digests, and are left out entirely
+/// when there is none. Blocks mined before votes existed therefore decode, and
+/// hash, exactly as they did.
+fn encode_vote<T: Output>(vote: &Option<Vote>, dest: &mut T) {
+	if let Some(vote) = vote {
+		vote.encode_to(dest);
+	}
+}
+
+fn decode_vote<I: Input>(input: &mut I) -> Result<Option<Vote>, codec::Error> {
+	match input.remaining_len()? {
+		Some(0) => Ok(None),
+		_ => Vote::decode(input).map(Some),
+	}
+}
+
+/// Seals and their `Compute` end with the author and vote in the same way.
+/// Blocks mined before seals named an author have neither, and a vote is only
+/// encoded after an author. Seals without an author therefore decode, and
+/// hash, exactly as the original `{ difficulty, work, nonce }` seals did.
+fn encode_author<T: Output>(author: &Option<AccountId>, vote: &Option<Vote>, dest: &mut T) {
+	if let Some(author) = author {
+		author.encode_to(dest);
+		encode_vote(vote, dest);
+	}
+}
+
+fn decode_author<I: Input>(input: &mut I) -> Result<(Option<AccountId>, Option<Vote>), codec::Error> {
+	match input.remaining_len()? {
+		Some(0) => Ok((None, None)),
+		_ => Ok((Some(AccountId::decode(input)?), decode_vote(input)?)),
+	}
+}
+
+/// The PoW pre-runtime digest of a block: who mined it, and what they vote for.
+///
+/// Blocks mined before votes existed carry just the encoded author, which
+/// decodes as a digest without a vote.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PreDigest<A = AccountId> {
+	pub author: A,
+	pub vote: Option<Vote>,
+}
+
+impl<A: Encode> Encode for PreDigest<A> {
+	fn encode_to<T: Output>(&self, dest: &mut T) {
+		self.author.encode_to(dest);
+		encode_vote(&self.vote, dest);
+	}
+}
+
+impl<A: Decode> Decode for PreDigest<A> {
+	fn decode<I: Input>(input: &mut I) -> Result<Self, codec::Error> {
+		Ok(PreDigest {
+			author: A::decode(input)?,
+			vote: decode_vote(input)?,
+		})
+	}
+}
+
+/// A Seal struct that will be encoded to a Vec<u8> as used as the
+/// RawSeal type
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct Seal {
+	pub difficulty: U256,
+	pub work: H256,
+	pub nonce: H256,
+	/// The account that did the work. Because it is part of the hashed
+	/// `Compute`, it can't be replaced without redoing the work. `None` for
+	/// blocks mined before seals named their author.
+	#[cfg_attr(feature = "std", serde(default))]
+	pub author: Option<AccountId>,
+	/// The author's vote, hashed along with the author. Seals without an
+	/// author can't vote.
+	#[cfg_attr(feature = "std", serde(default))]
+	pub vote: Option<Vote>,
+}
+
+impl Encode for Seal {
+	fn encode_to<T: Output>(&self, dest: &mut T) {
+		self.difficulty.encode_to(dest);
+		self.work.encode_to(dest);
+		self.nonce.encode_to(dest);
+		encode_author(&self.author, &self.vote, dest);
+	}
+}
+
+impl Decode for Seal {
+	fn decode<I: Input>(input: &mut I) -> Result<Self, codec::Error> {
+		let difficulty = Decode::decode(input)?;
+		let work = Decode::decode(input)?;
+		let nonce = Decode::decode(input)?;
+		let (author, vote) = decode_author(input)?;
+
+		Ok(Seal { difficulty, work, nonce, author, vote })
+	}
+}
+
+/// The data that is hashed to produce a seal's work.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Compute {
+	pub difficulty: U256,
+	pub pre_hash: H256,
+	pub nonce: H256,
+	pub author: Option<AccountId>,
+	pub vote: Option<Vote>,
+}
+
+impl Encode for Compute {
+	fn encode_to<T: Output>(&self, dest: &mut T) {
+		self.difficulty.encode_to(dest);
+		self.pre_hash.encode_to(dest);
+		self.nonce.encode_to(dest);
+		encode_author(&self.author, &self.vote, dest);
+	}
+}
+
+#[cfg(feature = "std")]
+impl Compute {
+	pub fn compute(self, algorithm: Algorithm) -> Seal {
+		let work = algorithm.hash(&self.encode()[..]);
+
+		Seal {
+			nonce: self.nonce,
+			difficulty: self.difficulty,
+			work,
+			author: self.author,
+			vote: self.vote,
+		}
+	}
+}
+
+/// Check that `seal` is a valid solution for `pre_hash` at the given difficulty,
+/// using the given algorithm.
+#[cfg(feature = "std")]
+pub fn verify_seal(seal: &Seal, pre_hash: &H256, difficulty: U256, algorithm: Algorithm) -> bool {
+	// See whether the hash meets the difficulty requirement. If not, fail fast.
+	if !hash_meets_difficulty(&seal.work, difficulty) {
+		return false
+	}
+
+	// Make sure the provided work actually comes from the correct pre_hash
+	let compute = Compute {
+		difficulty,
+		pre_hash: *pre_hash,
+		nonce: seal.nonce,
+		author: seal.author.clone(),
+		vote: seal.vote,
+	};
+
+	compute.compute(algorithm) == *seal
+}
+
+/// The work currently being mined, as offered to remote miners.
+#[cfg(feature = "std")]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Work {
+	/// Hash of the block being mined, without its seal.
+	pub pre_hash: H256,
+	/// Difficulty the seal must meet.
+	pub difficulty: U256,
+	/// The block being built on.
+	pub parent_hash: H256,
+	/// The author the block rewards. Seals must be computed for this account
+	/// if `bind_author` is set.
+	pub author: AccountId,
+	/// The hash algorithm seals must be computed with.
+	pub algorithm: Algorithm,
+	/// The author's vote. Seals must be computed with it if `bind_author` is set.
+	#[serde(default)]
+	pub vote: Option<Vote>,
+	/// Whether seals name the author and vote. Blocks below the chain's
+	/// `authorRequiredFrom` height, and every block of chains without it, have
+	/// the original `{ difficulty, work, nonce }` seals that all nodes accept.
+	#[serde(default)]
+	pub bind_author: bool,
+}
+
+#[cfg(feature = "std")]
+impl Work {
+	/// The data to hash for `nonce`. It names the author and vote only if the
+	/// work binds them.
+	pub fn compute(&self, nonce: H256) -> Compute {
+		let (author, vote) = if self.bind_author {
+			(Some(self.author.clone()), self.vote)
+		} else {
+			(None, None)
+		};
+
+		Compute {
+			difficulty: self.difficulty,
+			pre_hash: self.pre_hash,
+			nonce,
+			author,
+			vote,
+		}
+	}
+
+	/// Try up to `count` consecutive nonces, beginning at `first`, until one gives
+	/// a seal that meets the difficulty. Returns that seal, if any, and the number
+	/// of nonces tried.
+	pub fn mine_range(&self, first: U256, count: u32) -> (Option<Seal>, u32) {
+		let mut nonce = first;
+
+		for tried in 1..=count {
+			let seal = self.compute(u256_to_h256(nonce)).compute(self.algorithm);
+
+			if hash_meets_difficulty(&seal.work, self.difficulty) {
+				return (Some(seal), tried)
+			}
+
+			nonce = nonce.overflowing_add(U256::one()).0;
+		}
+
+		(None, count)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn compute(author: Option<AccountId>, vote: Option<Vote>) -> Compute {
+		Compute {
+			difficulty: U256::from(1),
+			pre_hash: H256::repeat_byte(1),
+			nonce: H256::repeat_byte(2),
+			author,
+			vote,
+		}
+	}
+
+	#[test]
+	fn legacy_seals_decode_and_verify() {
+		let difficulty = U256::from(1);
+		let pre_hash = H256::repeat_byte(1);
+		let nonce = H256::repeat_byte(2);
+		let work = Algorithm::Sha3.hash(&(difficulty, pre_hash, nonce).encode());
+		let encoded = (difficulty, work, nonce).encode();
+		assert_eq!(encoded.len(), 96);
+
+		let seal = Seal::decode(&mut &encoded[..]).unwrap();
+		assert_eq!(seal, Seal { difficulty, work, nonce, author: None, vote: None });
+		assert_eq!(seal.encode(), encoded);
+		assert!(verify_seal(&seal, &pre_hash, difficulty, Algorithm::Sha3));
+		assert_eq!(compute(None, None).compute(Algorithm::Sha3), seal);
+	}
+
+	#[test]
+	fn seals_round_trip_author_and_vote() {
+		let author = AccountId::new([3; 32]);
+		for vote in vec![None, Some(Vote::IncreaseReward), Some(Vote::DecreaseReward)] {
+			let seal = compute(Some(author.clone()), vote).compute(Algorithm::Sha3);
+			let decoded = Seal::decode(&mut &seal.encode()[..]).unwrap();
+
+			assert_eq!(decoded, seal);
+			assert!(verify_seal(&decoded, &H256::repeat_byte(1), U256::from(1), Algorithm::Sha3));
+		}
+	}
+
+	fn work(difficulty: U256) -> Work {
+		Work {
+			pre_hash: H256::repeat_byte(1),
+			difficulty,
+			parent_hash: H256::repeat_byte(5),
+			author: AccountId::new([3; 32]),
+			algorithm: Algorithm::Sha3,
+			vote: Some(Vote::IncreaseReward),
+			bind_author: true,
+		}
+	}
+
+	#[test]
+	fn nonces_are_big_endian() {
+		let mut expected = [0u8; 32];
+		expected[31] = 1;
+		expected[30] = 2;
+		assert_eq!(u256_to_h256(U256::from(0x0201)), H256::from(expected));
+	}
+
+	#[test]
+	fn mined_seals_verify() {
+		let work = work(U256::from(1));
+		let (seal, tried) = work.mine_range(U256::from(7), 10);
+		let seal = seal.unwrap();
+
+		// Every hash meets difficulty 1
+		assert_eq!(tried, 1);
+		assert_eq!(seal.nonce, u256_to_h256(U256::from(7)));
+		assert_eq!(seal.author, Some(work.author.clone()));
+		assert_eq!(seal.vote, work.vote);
+		assert!(verify_seal(&seal, &work.pre_hash, work.difficulty, work.algorithm));
+	}
+
+	#[test]
+	fn unbound_work_mines_legacy_seals() {
+		let work = Work { bind_author: false, ..work(U256::from(1)) };
+		let seal = work.mine_range(U256::from(7), 10).0.unwrap();
+
+		assert_eq!(seal.author, None);
+		assert_eq!(seal.vote, None);
+		assert_eq!(seal.encode().len(), 96);
+		assert!(verify_seal(&seal, &work.pre_hash, work.difficulty, work.algorithm));
+	}
+
+	#[test]
+	fn mining_tries_the_whole_range() {
+		let (seal, tried) = work(U256::max_value()).mine_range(U256::max_value(), 5);
+
+		// Practically no hash meets the highest difficulty, and nonces wrap around
+		assert_eq!(seal, None);
+		assert_eq!(tried, 5);
+	}
+
+	#[test]
+	fn replacing_the_author_invalidates_the_seal() {
+		let mut seal = compute(Some(AccountId::new([3; 32])), None).compute(Algorithm::Sha3);
+		seal.author = Some(AccountId::new([4; 32]));
+		assert!(!verify_seal(&seal, &H256::repeat_byte(1), U256::from(1), Algorithm::Sha3));
+
+		seal.author = None;
+		assert!(!verify_seal(&seal, &H256::repeat_byte(1), U256::from(1), Algorithm::Sha3));
+	}
+}
@@ -0,0 +1,43 @@
+/// A Pallet that lets governance manage the GRANDPA voter set.
+///
+/// PoW produces the blocks and GRANDPA, when a chain enables it, finalizes them.
+/// There is no staking or session rotation to pick the voters, so
+/// `VotersOrigin` sets them directly. The new set takes over once the block that scheduled the
+/// change is `delay` blocks deep in the finalized chain.
+
+use frame_support::{decl_module, decl_event, dispatch::DispatchResult, traits::EnsureOrigin};
+use grandpa::AuthorityList;
+use sp_std::prelude::*;
+
+pub trait Trait: system::Trait + grandpa::Trait {
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+	/// The origin allowed to set the voters.
+	type VotersOrigin: EnsureOrigin<Self::Origin>;
+}
+
+decl_module! {
+	/// The module declaration.
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		/// Replace the voter set with `voters`, `delay` blocks after this
+		/// block is finalized.
+		pub fn set_voters(origin, voters: AuthorityList, delay: T::BlockNumber) -> DispatchResult {
+			T::VotersOrigin::ensure_origin(origin)?;
+
+			<grandpa::Module<T>>::schedule_change(voters.clone(), delay, None)?;
+
+			Self::deposit_event(RawEvent::VotersScheduled(voters, delay));
+			Ok(())
+		}
+	}
+}
+
+decl_event!(
+	pub enum Event<T> where
+		BlockNumber = <T as system::Trait>::BlockNumber,
+	{
+		/// A new GRANDPA voter set has been scheduled to take over after the given delay
+		VotersScheduled(AuthorityList, BlockNumber),
+	}
+);
@@ -9,15 +9,18 @@
 include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
 
 use sp_std::prelude::*;
-use sp_core::{OpaqueMetadata, U256};
+use sp_core::{OpaqueMetadata, U256, u32_trait::{_1, _2, _3, _4, _5}};
 use sp_runtime::{
 	ApplyExtrinsicResult, transaction_validity::TransactionValidity, generic, create_runtime_str,
-	MultiSignature
+	MultiSignature, impl_opaque_keys,
 };
 use sp_runtime::traits::{
 	NumberFor, BlakeTwo256, Block as BlockT, StaticLookup, Verify, ConvertInto, IdentifyAccount
 };
 use sp_api::impl_runtime_apis;
+use codec::Codec;
+use grandpa::AuthorityList as GrandpaAuthorityList;
+use grandpa::fg_primitives;
 use sp_version::RuntimeVersion;
 #[cfg(feature = "std")]
 use sp_version::NativeVersion;
@@ -27,10 +30,10 @@ use sp_version::NativeVersion;
 pub use sp_runtime::BuildStorage;
 pub use timestamp::Call as TimestampCall;
 pub use balances::Call as BalancesCall;
-pub use sp_runtime::{Permill, Perbill};
+pub use sp_runtime::{Permill, Perbill, Percent};
 pub use frame_support::{
 	StorageValue, construct_runtime, parameter_types,
-	traits::Randomness,
+	traits::{Contains, Randomness},
 	weights::Weight,
 };
 
@@ -61,6 +64,11 @@ pub type Hash = sp_core::H256;
 pub type DigestItem = generic::DigestItem<Hash>;
 
 mod pow_params;
+pub use pow_params::{DifficultyMode, PowParamsApi, AlgorithmApi, EmissionApi, MedianTimeApi};
+
+mod grandpa_voters;
+
+mod sudo_retirement;
 
 /// Opaque types. These are used by the CLI to instantiate machinery that don't need to know
 /// the specifics of the runtime. They can then be made to be agnostic over specific formats
@@ -77,6 +85,12 @@ pub mod opaque {
 	pub type Block = generic::Block<Header, UncheckedExtrinsic>;
 	/// Opaque block identifier type.
 	pub type BlockId = generic::BlockId<Block>;
+
+	impl_opaque_keys! {
+		pub struct SessionKeys {
+			pub grandpa: Grandpa,
+		}
+	}
 }
 
 /// This runtime version.
@@ -84,7 +98,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("CLE-coin"),
 	impl_name: create_runtime_str!("CLE-coin"),
 	authoring_version: 1,
-	spec_version: 1,
+	spec_version: 15,
 	impl_version: 1,
 	apis: RUNTIME_API_VERSIONS,
 };
@@ -98,6 +112,9 @@ pub const MINUTES: BlockNumber = 60_000 / (MILLISECS_PER_BLOCK as BlockNumber);
 pub const HOURS: BlockNumber = MINUTES * 60;
 pub const DAYS: BlockNumber = HOURS * 24;
 
+/// One CLE, in the smallest unit. Balances have 12 decimals.
+pub const CLE: Balance = 1_000_000_000_000;
+
 /// The version infromation used to identify this runtime when compiled natively.
 #[cfg(feature = "std")]
 pub fn native_version() -> NativeVersion {
@@ -204,7 +221,7 @@ parameter_types! {
 
 impl transaction_payment::Trait for Runtime {
 	type Currency = balances::Module<Runtime>;
-	type OnTransactionPayment = ();
+	type OnTransactionPayment = PowParams;
 	type TransactionBaseFee = TransactionBaseFee;
 	type TransactionByteFee = TransactionByteFee;
 	type WeightToFee = ConvertInto;
@@ -216,9 +233,120 @@ impl sudo::Trait for Runtime {
 	type Proposal = Call;
 }
 
+impl sudo_retirement::Trait for Runtime {
+	type Event = Event;
+	type RetireOrigin = system::EnsureRoot<AccountId>;
+}
+
+type CouncilCollective = collective::Instance1;
+impl collective::Trait<CouncilCollective> for Runtime {
+	type Origin = Origin;
+	type Proposal = Call;
+	type Event = Event;
+}
+
+parameter_types! {
+	pub const LaunchPeriod: BlockNumber = 7 * DAYS;
+	pub const VotingPeriod: BlockNumber = 7 * DAYS;
+	pub const EmergencyVotingPeriod: BlockNumber = 3 * HOURS;
+	pub const MinimumDeposit: Balance = 100 * CLE;
+	pub const EnactmentPeriod: BlockNumber = 2 * DAYS;
+	pub const CooloffPeriod: BlockNumber = 7 * DAYS;
+	pub const PreimageByteDeposit: Balance = CLE / 100;
+}
+
+impl democracy::Trait for Runtime {
+	type Proposal = Call;
+	type Event = Event;
+	type Currency = Balances;
+	type EnactmentPeriod = EnactmentPeriod;
+	type LaunchPeriod = LaunchPeriod;
+	type VotingPeriod = VotingPeriod;
+	type EmergencyVotingPeriod = EmergencyVotingPeriod;
+	type MinimumDeposit = MinimumDeposit;
+	/// A majority of the council can put a proposal to the next external referendum.
+	type ExternalOrigin = collective::EnsureProportionAtLeast<_1, _2, AccountId, CouncilCollective>;
+	/// Three quarters of the council can make the next external referendum pass by simple majority.
+	type ExternalMajorityOrigin = collective::EnsureProportionAtLeast<_3, _4, AccountId, CouncilCollective>;
+	/// A unanimous council can make the next external referendum pass unless a majority rejects it.
+	type ExternalDefaultOrigin = collective::EnsureProportionAtLeast<_1, _1, AccountId, CouncilCollective>;
+	/// Three quarters of the council can table an external referendum immediately, with
+	/// a shorter voting and enactment period.
+	type FastTrackOrigin = collective::EnsureProportionAtLeast<_3, _4, AccountId, CouncilCollective>;
+	/// Three quarters of the council can cancel a referendum that has passed.
+	type CancellationOrigin = collective::EnsureProportionAtLeast<_3, _4, AccountId, CouncilCollective>;
+	/// Any council member can veto an external proposal for the cooloff period.
+	type VetoOrigin = collective::EnsureMember<AccountId, CouncilCollective>;
+	type CooloffPeriod = CooloffPeriod;
+	type PreimageByteDeposit = PreimageByteDeposit;
+	type Slash = Treasury;
+}
+
+/// The council members, who may tip on behalf of the treasury.
+pub struct CouncilTippers;
+impl Contains<AccountId> for CouncilTippers {
+	fn sorted_members() -> Vec<AccountId> {
+		let mut members = Council::members();
+		members.sort();
+		members
+	}
+}
+
+parameter_types! {
+	pub const ProposalBond: Permill = Permill::from_percent(5);
+	pub const ProposalBondMinimum: Balance = CLE;
+	pub const SpendPeriod: BlockNumber = 7 * DAYS;
+	pub const Burn: Permill = Permill::zero();
+	pub const TipCountdown: BlockNumber = DAYS;
+	pub const TipFindersFee: Percent = Percent::from_percent(20);
+	pub const TipReportDepositBase: Balance = CLE;
+	pub const TipReportDepositPerByte: Balance = CLE / 100;
+}
+
+impl treasury::Trait for Runtime {
+	type Currency = Balances;
+	/// Three fifths of the council approve spending.
+	type ApproveOrigin = collective::EnsureProportionAtLeast<_3, _5, AccountId, CouncilCollective>;
+	/// A majority of the council rejects spending.
+	type RejectOrigin = collective::EnsureProportionMoreThan<_1, _2, AccountId, CouncilCollective>;
+	type Tippers = CouncilTippers;
+	type TipCountdown = TipCountdown;
+	type TipFindersFee = TipFindersFee;
+	type TipReportDepositBase = TipReportDepositBase;
+	type TipReportDepositPerByte = TipReportDepositPerByte;
+	type Event = Event;
+	type ProposalRejection = Treasury;
+	type ProposalBond = ProposalBond;
+	type ProposalBondMinimum = ProposalBondMinimum;
+	type SpendPeriod = SpendPeriod;
+	type Burn = Burn;
+}
+
+impl grandpa::Trait for Runtime {
+	type Event = Event;
+}
+
+impl grandpa_voters::Trait for Runtime {
+	type Event = Event;
+	type VotersOrigin = system::EnsureRoot<AccountId>;
+}
+
+parameter_types! {
+	pub const TargetBlockTime: u64 = MILLISECS_PER_BLOCK;
+	pub const VoteWindow: u32 = DAYS;
+	pub const VoteThreshold: Perbill = Perbill::from_percent(75);
+	pub const RewardAdjustment: Perbill = Perbill::from_percent(5);
+}
+
 impl pow_params::Trait for Runtime {
 	type Event = Event;
 	type RewardCurrency = Balances;
+	type TargetBlockTime = TargetBlockTime;
+	type ParamsOrigin = system::EnsureRoot<AccountId>;
+	type VoteWindow = VoteWindow;
+	type VoteThreshold = VoteThreshold;
+	type RewardAdjustment = RewardAdjustment;
+	type Treasury = Treasury;
 }
 
 construct_runtime!(
@@ -235,6 +363,12 @@ construct_runtime!(
 		Sudo: sudo,
 		RandomnessCollectiveFlip: randomness_collective_flip::{Module, Call, Storage},
 		PowParams: pow_params::{Module, Call, Storage, Event<T>, Config<T>},
+		Grandpa: grandpa::{Module, Call, Storage, Config, Event},
+		GrandpaVoters: grandpa_voters::{Module, Call, Event<T>},
+		Council: collective::<Instance1>::{Module, Call, Storage, Origin<T>, Event<T>, Config<T>},
+		Democracy: democracy::{Module, Call, Storage, Config, Event<T>},
+		Treasury: treasury::{Module, Call, Storage, Event<T>},
+		SudoRetirement: sudo_retirement::{Module, Call, Event<T>},
 	}
 );
 
@@ -259,11 +393,21 @@ pub type SignedExtra = (
 );
 /// Unchecked extrinsic type as expected by this runtime.
 pub type UncheckedExtrinsic = generic::UncheckedExtrinsic<Address, Call, Signature, SignedExtra>;
+/// The payload being signed in transactions.
+pub type SignedPayload = generic::SignedPayload<Call, SignedExtra>;
 /// Extrinsic type that has already been checked.
 pub type CheckedExtrinsic = generic::CheckedExtrinsic<AccountId, Call, SignedExtra>;
 /// Executive: handles dispatch to the various modules.
 pub type Executive = frame_executive::Executive<Runtime, Block, system::ChainContext<Runtime>, Runtime, AllModules>;
 
+sp_api::decl_runtime_apis! {
+	/// Reports the funds held by the treasury.
+	pub trait TreasuryApi<Balance> where Balance: Codec {
+		/// The free balance of the treasury's account.
+		fn treasury_balance() -> Balance;
+	}
+}
+
 impl_runtime_apis! {
 	impl sp_api::Core<Block> for Runtime {
 		fn version() -> RuntimeVersion {
@@ -323,10 +467,14 @@ impl_runtime_apis! {
 	}
 
 	impl sp_session::SessionKeys<Block> for Runtime {
-		fn generate_session_keys(_seed: Option<Vec<u8>>) -> Vec<u8> {
-			// There are no session keys, so just return an empty vec
-			//opaque::SessionKeys::generate(seed)
-			Vec::new()
+		fn generate_session_keys(seed: Option<Vec<u8>>) -> Vec<u8> {
+			opaque::SessionKeys::generate(seed)
+		}
+	}
+
+	impl fg_primitives::GrandpaApi<Block> for Runtime {
+		fn grandpa_authorities() -> GrandpaAuthorityList {
+			Grandpa::grandpa_authorities()
 		}
 	}
 
@@ -341,4 +489,38 @@ impl_runtime_apis! {
 			pow_params::Module::<Runtime>::difficulty()
 		}
 	}
+
+	impl pow_params::PowParamsApi<Block, Balance> for Runtime {
+		fn reward() -> Balance {
+			pow_params::Module::<Runtime>::author_reward(System::block_number() + 1)
+		}
+	}
+
+	impl pow_params::EmissionApi<Block, BlockNumber, Balance> for Runtime {
+		fn reward_at(n: BlockNumber) -> Balance {
+			pow_params::Module::<Runtime>::reward_at(n)
+		}
+
+		fn projected_issuance(n: BlockNumber) -> Balance {
+			pow_params::Module::<Runtime>::projected_issuance(n)
+		}
+	}
+
+	impl pow_params::AlgorithmApi<Block> for Runtime {
+		fn algorithm() -> cle_coin_pow::Algorithm {
+			pow_params::Module::<Runtime>::next_algorithm()
+		}
+	}
+
+	impl pow_params::MedianTimeApi<Block> for Runtime {
+		fn median_time_past() -> u64 {
+			pow_params::Module::<Runtime>::median_time_past()
+		}
+	}
+
+	impl self::TreasuryApi<Block, Balance> for Runtime {
+		fn treasury_balance() -> Balance {
+			Balances::free_balance(&Treasury::account_id())
+		}
+	}
 }
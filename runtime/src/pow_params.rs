@@ -4,23 +4,128 @@
 /// The parameters controlled are:
 /// * Difficulty
 /// * BlockReward
+/// * HalvingInterval
+/// * MaxSupply
+///
+/// The block reward is minted to the block author at the end of each block.
+/// Miners declare themselves as the author in a PoW pre-runtime digest.
+///
+/// `TreasuryRewardShare` of each block reward is paid to `Treasury` instead
+/// of the author. Transaction fees are split too. `FeeAuthorShare` of each fee
+/// goes to the block author, `FeeTreasuryShare` to `Treasury`, and the rest is
+/// burned.
+///
+/// `Reward` is the reward of the first blocks. It halves every
+/// `HalvingInterval` blocks, counted from genesis, and issuance stops once
+/// the total issuance reaches `MaxSupply`. A zero interval never halves, and a
+/// zero supply is uncapped.
+///
+/// The same digest can carry the miner's vote to raise or lower the reward.
+/// Votes are tallied over the last `VoteWindow` blocks. Once `VoteThreshold`
+/// of the window votes the same way, the reward changes by `RewardAdjustment`,
+/// at most once per window.
+///
+/// Every parameter change must come from `ParamsOrigin`. The runtime accepts
+/// root, the origin that passed referenda are dispatched with.
+///
+/// The difficulty is either set manually by governance, or retargeted
+/// automatically at the end of every block so that block times approach
+/// `TargetBlockTime`. The retargeting algorithm is the damped and clamped
+/// moving window used by Kulupu. Governance can override the difficulty in
+/// either mode, which restarts the moving window from the new value.
+///
+/// The hash algorithm seals must use is also stored here. Governance can
+/// schedule a switch to another algorithm at a future block height, which the
+/// node picks up through `AlgorithmApi` when importing and mining that block.
 
-use frame_support::{decl_module, decl_storage, decl_event, dispatch::DispatchResult};
-use system::ensure_root;
+use frame_support::{decl_module, decl_storage, decl_event, ensure, dispatch::DispatchResult};
 use sp_core::U256;
-use frame_support::traits::Currency;
+use sp_consensus_pow::POW_ENGINE_ID;
+use sp_runtime::{Perbill, RuntimeDebug, traits::{CheckedAdd, CheckedMul, One, Saturating, SimpleArithmetic, UniqueSaturatedInto, Zero}};
+use sp_std::{cmp::{min, max}, prelude::*};
+use frame_support::traits::{Currency, EnsureOrigin, Get, Imbalance, OnUnbalanced};
+use codec::{Codec, Encode, Decode};
+use cle_coin_pow::{Algorithm, PreDigest, Vote};
+#[cfg(feature = "std")]
+use serde::{Serialize, Deserialize};
 
-pub trait Trait: system::Trait {
+/// Number of past blocks considered when retargeting the difficulty.
+pub const DIFFICULTY_ADJUST_WINDOW: u64 = 60;
+/// How strongly the observed block time is pulled towards the target. Higher is smoother.
+const DIFFICULTY_DAMP_FACTOR: u64 = 3;
+/// The observed block time is clamped to within this factor of the target.
+const CLAMP_FACTOR: u64 = 2;
+/// Retargeting never lowers the difficulty below this value.
+const MIN_DIFFICULTY: u64 = 1000;
+/// Number of past blocks whose median timestamp new blocks must not precede.
+const MEDIAN_TIME_SPAN: usize = 11;
+
+pub trait Trait: system::Trait + timestamp::Trait {
 	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
 	type RewardCurrency: Currency<Self::AccountId>;
+	/// The block time, in milliseconds, that difficulty retargeting aims for.
+	type TargetBlockTime: Get<u64>;
+	/// The origin allowed to change the parameters.
+	type ParamsOrigin: EnsureOrigin<Self::Origin>;
+	/// Number of recent blocks whose votes are tallied.
+	type VoteWindow: Get<u32>;
+	/// The share of the window that must vote for a change to apply it.
+	type VoteThreshold: Get<Perbill>;
+	/// How much of the reward a successful vote adds or removes.
+	type RewardAdjustment: Get<Perbill>;
+	/// Receives the treasury's share of block rewards and transaction fees.
+	type Treasury: OnUnbalanced<NegativeImbalanceOf<Self>>;
 }
 
 type BalanceOf<T> = <<T as Trait>::RewardCurrency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type NegativeImbalanceOf<T> =
+	<<T as Trait>::RewardCurrency as Currency<<T as system::Trait>::AccountId>>::NegativeImbalance;
+
+/// How the PoW difficulty is maintained.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum DifficultyMode {
+	/// The difficulty only changes when governance sets it.
+	Manual,
+	/// The difficulty is retargeted at the end of every block.
+	Automatic,
+}
+
+/// The difficulty and timestamp of a past block, as used for retargeting.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, Default, RuntimeDebug)]
+pub struct DifficultyAndTimestamp {
+	pub difficulty: U256,
+	pub timestamp: u64,
+}
 
 decl_storage! {
 	trait Store for Module<T: Trait> as TemplateModule {
 		Difficulty get(fn difficulty) config(): U256 = 5000.into();
 		Reward get(fn reward) config(): BalanceOf<T> = 1.into();
+		HalvingInterval get(fn halving_interval) config(): T::BlockNumber;
+		MaxSupply get(fn max_supply) config(): BalanceOf<T>;
+		/// The share of each transaction fee paid to the block author.
+		FeeAuthorShare get(fn fee_author_share) config(): Perbill;
+		/// The share of each transaction fee paid to the treasury.
+		FeeTreasuryShare get(fn fee_treasury_share) config(): Perbill;
+		/// The share of each block reward paid to the treasury.
+		TreasuryRewardShare get(fn treasury_reward_share) config(): Perbill;
+		Mode get(fn mode) config(): DifficultyMode = DifficultyMode::Manual;
+		/// The most recent blocks' difficulties and timestamps, oldest first.
+		PastDifficultiesAndTimestamps get(fn past_difficulties_and_timestamps): Vec<DifficultyAndTimestamp>;
+		/// The timestamps of the last `MEDIAN_TIME_SPAN` blocks, oldest first. Unlike
+		/// the retargeting window, setting the difficulty doesn't clear them.
+		RecentTimestamps get(fn recent_timestamps): Vec<u64>;
+		/// The hash algorithm seals of the current block use.
+		CurrentAlgorithm get(fn current_algorithm) config(): Algorithm;
+		/// A pending algorithm switch, and the first block that uses the new algorithm.
+		ScheduledAlgorithm get(fn scheduled_algorithm): Option<(T::BlockNumber, Algorithm)>;
+		/// The votes of the last `VoteWindow` blocks. Block `n`'s vote is in slot `n % VoteWindow`.
+		VoteSlots get(fn vote_slot): map u32 => Option<Vote>;
+		/// The number of votes in `VoteSlots` to increase and to decrease the reward.
+		VoteTally get(fn vote_tally): (u32, u32);
+		/// The block in which votes last changed the reward.
+		LastVotedAdjustment get(fn last_voted_adjustment): T::BlockNumber;
 	}
 }
 
@@ -30,30 +135,483 @@ decl_module! {
 		fn deposit_event() = default;
 
 		pub fn set_difficulty(origin, new_difficulty: U256) -> DispatchResult {
-			ensure_root(origin)?;
+			T::ParamsOrigin::ensure_origin(origin)?;
 
 			Difficulty::put(new_difficulty);
+			PastDifficultiesAndTimestamps::kill();
 
 			Self::deposit_event(RawEvent::DifficultySet(new_difficulty));
 			Ok(())
 		}
 
 		pub fn set_reward(origin, new_reward: BalanceOf<T>) -> DispatchResult {
-			ensure_root(origin)?;
+			T::ParamsOrigin::ensure_origin(origin)?;
 
 			Reward::<T>::put(new_reward);
 
 			Self::deposit_event(RawEvent::RewardSet(new_reward));
 			Ok(())
 		}
+
+		pub fn set_halving_interval(origin, new_interval: T::BlockNumber) -> DispatchResult {
+			T::ParamsOrigin::ensure_origin(origin)?;
+
+			HalvingInterval::<T>::put(new_interval);
+
+			Self::deposit_event(RawEvent::HalvingIntervalSet(new_interval));
+			Ok(())
+		}
+
+		pub fn set_max_supply(origin, new_max_supply: BalanceOf<T>) -> DispatchResult {
+			T::ParamsOrigin::ensure_origin(origin)?;
+
+			MaxSupply::<T>::put(new_max_supply);
+
+			Self::deposit_event(RawEvent::MaxSupplySet(new_max_supply));
+			Ok(())
+		}
+
+		/// Pay `author_share` of each fee to the block author and
+		/// `treasury_share` to the treasury, and burn the rest.
+		pub fn set_fee_shares(origin, author_share: Perbill, treasury_share: Perbill) -> DispatchResult {
+			T::ParamsOrigin::ensure_origin(origin)?;
+			ensure!(
+				author_share.deconstruct().saturating_add(treasury_share.deconstruct()) <= Perbill::one().deconstruct(),
+				"Fee shares must not add up to more than the whole fee"
+			);
+
+			FeeAuthorShare::put(author_share);
+			FeeTreasuryShare::put(treasury_share);
+
+			Self::deposit_event(RawEvent::FeeSharesSet(author_share, treasury_share));
+			Ok(())
+		}
+
+		pub fn set_treasury_reward_share(origin, new_share: Perbill) -> DispatchResult {
+			T::ParamsOrigin::ensure_origin(origin)?;
+
+			TreasuryRewardShare::put(new_share);
+
+			Self::deposit_event(RawEvent::TreasuryRewardShareSet(new_share));
+			Ok(())
+		}
+
+		pub fn set_difficulty_mode(origin, new_mode: DifficultyMode) -> DispatchResult {
+			T::ParamsOrigin::ensure_origin(origin)?;
+
+			Mode::put(new_mode);
+
+			Self::deposit_event(RawEvent::DifficultyModeSet(new_mode));
+			Ok(())
+		}
+
+		/// Switch to `algorithm` starting with block `at`. Replaces any switch
+		/// that is already scheduled.
+		pub fn schedule_algorithm_switch(origin, at: T::BlockNumber, algorithm: Algorithm) -> DispatchResult {
+			T::ParamsOrigin::ensure_origin(origin)?;
+			ensure!(at > <system::Module<T>>::block_number(), "Algorithm switch must be scheduled for a future block");
+
+			ScheduledAlgorithm::<T>::put((at, algorithm));
+
+			Self::deposit_event(RawEvent::AlgorithmSwitchScheduled(at, algorithm));
+			Ok(())
+		}
+
+		pub fn cancel_algorithm_switch(origin) -> DispatchResult {
+			T::ParamsOrigin::ensure_origin(origin)?;
+			ensure!(ScheduledAlgorithm::<T>::exists(), "No algorithm switch is scheduled");
+
+			ScheduledAlgorithm::<T>::kill();
+
+			Self::deposit_event(RawEvent::AlgorithmSwitchCancelled);
+			Ok(())
+		}
+
+		fn on_initialize(n: T::BlockNumber) {
+			if let Some((at, algorithm)) = Self::scheduled_algorithm() {
+				if n >= at {
+					CurrentAlgorithm::put(algorithm);
+					ScheduledAlgorithm::<T>::kill();
+
+					Self::deposit_event(RawEvent::AlgorithmSwitched(algorithm));
+				}
+			}
+		}
+
+		fn on_finalize(n: T::BlockNumber) {
+			let digest = Self::pre_digest();
+
+			if let Some(PreDigest { author, .. }) = &digest {
+				let reward = Self::author_reward(n);
+				let to_treasury = Self::block_reward(n).saturating_sub(reward);
+
+				// Dropping the positive imbalance increases the total issuance. It
+				// is empty if the reward is too small to create the author's account.
+				let paid = T::RewardCurrency::deposit_creating(author, reward);
+				let reward = paid.peek();
+				drop(paid);
+				if !to_treasury.is_zero() {
+					T::Treasury::on_unbalanced(T::RewardCurrency::issue(to_treasury));
+				}
+
+				Self::deposit_event(RawEvent::RewardPaid(author.clone(), reward));
+			}
+
+			Self::tally_vote(n, digest.and_then(|d| d.vote));
+			Self::record_timestamp();
+			Self::retarget();
+		}
 	}
 }
 
 decl_event!(
-	pub enum Event<T> where Balance = BalanceOf<T> {
+	pub enum Event<T> where
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>,
+		BlockNumber = <T as system::Trait>::BlockNumber,
+	{
 		/// The PoW Difficulty has been set
 		DifficultySet(U256),
 		/// The Block Reward has bee nset
 		RewardSet(Balance),
+		/// The Halving Interval has been set
+		HalvingIntervalSet(BlockNumber),
+		/// The Max Supply has been set
+		MaxSupplySet(Balance),
+		/// The Block Reward, less the treasury's share, has been paid to the block's author
+		RewardPaid(AccountId, Balance),
+		/// The difficulty mode has been set
+		DifficultyModeSet(DifficultyMode),
+		/// The difficulty has been retargeted automatically
+		DifficultyRetargeted(U256),
+		/// A switch to a new hash algorithm has been scheduled for the given block
+		AlgorithmSwitchScheduled(BlockNumber, Algorithm),
+		/// The scheduled algorithm switch has been cancelled
+		AlgorithmSwitchCancelled,
+		/// Seals now use the given hash algorithm
+		AlgorithmSwitched(Algorithm),
+		/// Miners voted the Block Reward to the given value
+		RewardVoted(Balance),
+		/// The shares of fees paid to the author and the treasury have been set
+		FeeSharesSet(Perbill, Perbill),
+		/// The share of block rewards paid to the treasury has been set
+		TreasuryRewardShareSet(Perbill),
+		/// A transaction fee has been split between the block author (if the
+		/// block has one), the treasury and burning, in that order
+		FeeDistributed(Option<AccountId>, Balance, Balance, Balance),
 	}
 );
+
+impl<T: Trait> Module<T> {
+	/// The current block's PoW pre-runtime digest. Blocks without such a
+	/// digest (or with one that doesn't decode) have no author and no vote.
+	fn pre_digest() -> Option<PreDigest<T::AccountId>> {
+		<system::Module<T>>::digest()
+			.logs
+			.iter()
+			.filter_map(|item| item.as_pre_runtime())
+			.find(|(id, _)| *id == POW_ENGINE_ID)
+			.and_then(|(_, mut data)| PreDigest::decode(&mut data).ok())
+	}
+
+	/// The author of the current block as declared in the PoW pre-runtime digest.
+	pub fn author() -> Option<T::AccountId> {
+		Self::pre_digest().map(|digest| digest.author)
+	}
+
+	/// The reward the emission schedule pays for block `n`, before the supply cap.
+	pub fn reward_at(n: T::BlockNumber) -> BalanceOf<T> {
+		halve(Self::reward(), Self::halvings(n))
+	}
+
+	/// The reward paid for block `n` given the current total issuance: the
+	/// scheduled reward, cut to what is left below the supply cap.
+	pub fn block_reward(n: T::BlockNumber) -> BalanceOf<T> {
+		let reward = Self::reward_at(n);
+		let max_supply = Self::max_supply();
+		if max_supply.is_zero() {
+			return reward
+		}
+
+		min(reward, max_supply.saturating_sub(T::RewardCurrency::total_issuance()))
+	}
+
+	/// The part of block `n`'s reward paid to its author, after the treasury's share.
+	pub fn author_reward(n: T::BlockNumber) -> BalanceOf<T> {
+		let total = Self::block_reward(n);
+		total.saturating_sub(Self::treasury_reward_share() * total)
+	}
+
+	/// The total issuance after block `n`, if every block until then pays
+	/// its scheduled reward and nothing else changes the issuance. Only
+	/// blocks after the current one are projected.
+	pub fn projected_issuance(n: T::BlockNumber) -> BalanceOf<T> {
+		let mut issuance = T::RewardCurrency::total_issuance();
+		let mut next = <system::Module<T>>::block_number() + One::one();
+		let interval = Self::halving_interval();
+
+		while next <= n {
+			// The last block paying the same reward as `next`. The next halving
+			// may be beyond the largest block number, and so is `n` then.
+			let last = if interval.is_zero() {
+				n
+			} else {
+				(next / interval).checked_add(&One::one())
+					.and_then(|halvings| halvings.checked_mul(&interval))
+					.map_or(n, |halving| min(n, halving - One::one()))
+			};
+			let blocks: BalanceOf<T> = UniqueSaturatedInto::<u32>::unique_saturated_into(last - next + One::one()).into();
+			let reward = Self::reward_at(next);
+			if reward.is_zero() {
+				break
+			}
+
+			issuance = issuance.saturating_add(reward.saturating_mul(blocks));
+			if last == n {
+				break
+			}
+			next = last + One::one();
+		}
+
+		let max_supply = Self::max_supply();
+		if max_supply.is_zero() {
+			issuance
+		} else {
+			min(issuance, max(max_supply, T::RewardCurrency::total_issuance()))
+		}
+	}
+
+	/// The number of times the reward has halved by block `n`.
+	fn halvings(n: T::BlockNumber) -> u32 {
+		let interval = Self::halving_interval();
+		if interval.is_zero() {
+			return 0
+		}
+
+		(n / interval).unique_saturated_into()
+	}
+
+	/// Record block `n`'s vote in place of the vote that just left the window,
+	/// and adjust the reward if enough of the window agrees.
+	fn tally_vote(n: T::BlockNumber, vote: Option<Vote>) {
+		let window = T::VoteWindow::get();
+		if window == 0 {
+			return
+		}
+
+		let slot = UniqueSaturatedInto::<u32>::unique_saturated_into(n) % window;
+		let (mut increase, mut decrease) = Self::vote_tally();
+		match VoteSlots::take(slot) {
+			Some(Vote::IncreaseReward) => increase = increase.saturating_sub(1),
+			Some(Vote::DecreaseReward) => decrease = decrease.saturating_sub(1),
+			None => (),
+		}
+		match vote {
+			Some(Vote::IncreaseReward) => increase += 1,
+			Some(Vote::DecreaseReward) => decrease += 1,
+			None => (),
+		}
+		if let Some(vote) = vote {
+			VoteSlots::insert(slot, vote);
+		}
+		VoteTally::put((increase, decrease));
+
+		// Give the window time to refill with votes cast under the new reward.
+		let last = Self::last_voted_adjustment();
+		if !last.is_zero() && n.saturating_sub(last) < window.into() {
+			return
+		}
+
+		let needed = max(T::VoteThreshold::get() * window, 1);
+		let reward = Self::reward();
+		// Small rewards still move, rather than the share rounding down to nothing
+		let adjustment = max(T::RewardAdjustment::get() * reward, One::one());
+		let new_reward = if increase >= needed {
+			reward.saturating_add(adjustment)
+		} else if decrease >= needed {
+			reward.saturating_sub(adjustment)
+		} else {
+			return
+		};
+		// A reward that can't move further isn't an adjustment
+		if new_reward == reward {
+			return
+		}
+
+		Reward::<T>::put(new_reward);
+		LastVotedAdjustment::<T>::put(n);
+		Self::deposit_event(RawEvent::RewardVoted(new_reward));
+	}
+
+	/// The hash algorithm that seals of the next block must use, taking a
+	/// switch scheduled for that block into account.
+	pub fn next_algorithm() -> Algorithm {
+		let next = <system::Module<T>>::block_number() + One::one();
+		match Self::scheduled_algorithm() {
+			Some((at, algorithm)) if next >= at => algorithm,
+			_ => Self::current_algorithm(),
+		}
+	}
+
+	/// The median timestamp of the last `MEDIAN_TIME_SPAN` blocks, or of as many
+	/// as there are. Zero if there are none.
+	pub fn median_time_past() -> u64 {
+		let mut timestamps = Self::recent_timestamps();
+		// Runtimes from before `RecentTimestamps` only kept the retargeting window
+		if timestamps.is_empty() {
+			timestamps = Self::past_difficulties_and_timestamps().iter()
+				.rev()
+				.take(MEDIAN_TIME_SPAN)
+				.map(|d| d.timestamp)
+				.collect();
+		}
+		timestamps.sort();
+
+		timestamps.get(timestamps.len() / 2).cloned().unwrap_or(0)
+	}
+
+	/// Record the current block's timestamp for `median_time_past`.
+	fn record_timestamp() {
+		let mut timestamps = Self::recent_timestamps();
+		timestamps.push(<timestamp::Module<T>>::get().unique_saturated_into());
+		let excess = timestamps.len().saturating_sub(MEDIAN_TIME_SPAN);
+		timestamps.drain(..excess);
+
+		RecentTimestamps::put(timestamps);
+	}
+
+	/// Record the current block in the moving window and, in automatic mode,
+	/// compute the difficulty for the next block.
+	///
+	/// The window is maintained in both modes so that switching to automatic
+	/// mode starts from real data.
+	fn retarget() {
+		let mut window = Self::past_difficulties_and_timestamps();
+		window.push(DifficultyAndTimestamp {
+			difficulty: Self::difficulty(),
+			timestamp: <timestamp::Module<T>>::get().unique_saturated_into(),
+		});
+		let excess = window.len().saturating_sub(DIFFICULTY_ADJUST_WINDOW as usize);
+		window.drain(..excess);
+
+		if Self::mode() == DifficultyMode::Automatic {
+			let next = Self::next_difficulty(&window);
+			Difficulty::put(next);
+			Self::deposit_event(RawEvent::DifficultyRetargeted(next));
+		}
+
+		PastDifficultiesAndTimestamps::put(window);
+	}
+
+	/// The difficulty that brings the window's average block time to the target.
+	///
+	/// Blocks missing from a partially filled window are assumed to have been
+	/// mined on target at the current difficulty.
+	fn next_difficulty(window: &[DifficultyAndTimestamp]) -> U256 {
+		let target = T::TargetBlockTime::get();
+		let missing = DIFFICULTY_ADJUST_WINDOW.saturating_sub(window.len() as u64);
+
+		let ts_delta = window
+			.windows(2)
+			.map(|pair| pair[1].timestamp.saturating_sub(pair[0].timestamp))
+			.fold(missing.saturating_mul(target), u64::saturating_add);
+
+		let diff_sum = window
+			.iter()
+			.fold(Self::difficulty().saturating_mul(missing.into()), |sum, d| sum.saturating_add(d.difficulty));
+
+		// Pull the observed time towards the goal, then bound how far it can be from it.
+		let goal = (DIFFICULTY_ADJUST_WINDOW - 1) * target;
+		let adjusted = clamp(damp(ts_delta, goal, DIFFICULTY_DAMP_FACTOR), goal, CLAMP_FACTOR);
+
+		let next = diff_sum.saturating_mul(goal.into())
+			/ U256::from(max(adjusted, 1)).saturating_mul(DIFFICULTY_ADJUST_WINDOW.into());
+
+		max(U256::from(MIN_DIFFICULTY), next)
+	}
+}
+
+sp_api::decl_runtime_apis! {
+	/// Exposes the parameters stored in this pallet to the node.
+	pub trait PowParamsApi<Balance> where Balance: Codec {
+		/// The reward paid to the author of the next block, after the
+		/// treasury's share.
+		fn reward() -> Balance;
+	}
+
+	/// Reports the hash algorithm seals must use. Runtimes without this API
+	/// leave the choice to the chain spec.
+	pub trait AlgorithmApi {
+		/// The algorithm for seals of the next block.
+		fn algorithm() -> Algorithm;
+	}
+
+	/// Reports the emission schedule.
+	pub trait EmissionApi<BlockNumber, Balance> where BlockNumber: Codec, Balance: Codec {
+		/// The reward the schedule pays for block `n`, before the supply cap.
+		fn reward_at(n: BlockNumber) -> Balance;
+		/// The total issuance after block `n`, if every block until then pays its reward.
+		fn projected_issuance(n: BlockNumber) -> Balance;
+	}
+
+	/// Reports how early the next block's timestamp may be.
+	pub trait MedianTimeApi {
+		/// The median timestamp of the most recent blocks. The next block's
+		/// timestamp must not be earlier.
+		fn median_time_past() -> u64;
+	}
+}
+
+impl<T: Trait> OnUnbalanced<NegativeImbalanceOf<T>> for Module<T> {
+	/// Split a transaction fee between the block author, the treasury and
+	/// burning. The author's share of fees in blocks without an author is burned,
+	/// as is a share too small to create the author's account.
+	fn on_unbalanced(fee: NegativeImbalanceOf<T>) {
+		let total = fee.peek();
+		let author = Self::author();
+
+		let (to_author, rest) = match &author {
+			Some(author) => {
+				let (share, rest) = fee.split(Self::fee_author_share() * total);
+				let paid = T::RewardCurrency::deposit_creating(author, share.peek());
+				let to_author = paid.peek();
+				// Whatever wasn't deposited is burned with the rest.
+				let unpaid = share.offset(paid).unwrap_or_else(|_| Imbalance::zero());
+				(to_author, rest.merge(unpaid))
+			}
+			None => (Zero::zero(), fee),
+		};
+
+		let (treasury, burned) = rest.split(Self::fee_treasury_share() * total);
+		let (to_treasury, to_burn) = (treasury.peek(), burned.peek());
+		T::Treasury::on_unbalanced(treasury);
+
+		// Dropping the negative imbalance decreases the total issuance.
+		drop(burned);
+
+		Self::deposit_event(RawEvent::FeeDistributed(author, to_author, to_treasury, to_burn));
+	}
+}
+
+/// `reward` halved `halvings` times, rounding down.
+fn halve<Balance: SimpleArithmetic>(reward: Balance, halvings: u32) -> Balance {
+	let mut reward = reward;
+	for _ in 0..halvings {
+		if reward.is_zero() {
+			break
+		}
+		reward = reward / Balance::from(2u32);
+	}
+	reward
+}
+
+fn damp(actual: u64, goal: u64, damp_factor: u64) -> u64 {
+	(actual + (damp_factor - 1) * goal) / damp_factor
+}
+
+fn clamp(actual: u64, goal: u64, clamp_factor: u64) -> u64 {
+	max(goal / clamp_factor, min(actual, goal * clamp_factor))
+}
+
+#[cfg(test)]
+mod tests;
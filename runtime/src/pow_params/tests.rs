@@ -0,0 +1,652 @@
+//! Tests for the pow_params pallet, run against a minimal mock runtime.
+
+use super::*;
+use frame_support::{assert_ok, assert_noop, impl_outer_event, impl_outer_origin, parameter_types, weights::Weight};
+use sp_core::H256;
+use sp_runtime::{
+	Perbill,
+	testing::{Digest, DigestItem, Header},
+	traits::{BlakeTwo256, IdentityLookup, OnFinalize, OnInitialize},
+};
+
+impl_outer_origin! {
+	pub enum Origin for Test {}
+}
+
+mod pow_params {
+	pub use super::super::Event;
+}
+
+impl_outer_event! {
+	pub enum TestEvent for Test {
+		balances<T>,
+		pow_params<T>,
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Test;
+
+parameter_types! {
+	pub const BlockHashCount: u64 = 250;
+	pub const MaximumBlockWeight: Weight = 1024;
+	pub const MaximumBlockLength: u32 = 2 * 1024;
+	pub const AvailableBlockRatio: Perbill = Perbill::one();
+}
+
+impl system::Trait for Test {
+	type Origin = Origin;
+	type Call = ();
+	type Index = u64;
+	type BlockNumber = u64;
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type AccountId = u64;
+	type Lookup = IdentityLookup<u64>;
+	type Header = Header;
+	type Event = TestEvent;
+	type BlockHashCount = BlockHashCount;
+	type MaximumBlockWeight = MaximumBlockWeight;
+	type MaximumBlockLength = MaximumBlockLength;
+	type AvailableBlockRatio = AvailableBlockRatio;
+	type Version = ();
+	type ModuleToIndex = ();
+}
+
+parameter_types! {
+	pub const MinimumPeriod: u64 = 1;
+}
+
+impl timestamp::Trait for Test {
+	type Moment = u64;
+	type OnTimestampSet = ();
+	type MinimumPeriod = MinimumPeriod;
+}
+
+parameter_types! {
+	// The same as the runtime's, so that small payments are lost as they are there.
+	pub const ExistentialDeposit: u64 = 500;
+	pub const TransferFee: u64 = 0;
+	pub const CreationFee: u64 = 0;
+}
+
+impl balances::Trait for Test {
+	type Balance = u64;
+	type OnFreeBalanceZero = ();
+	type OnNewAccount = ();
+	type Event = TestEvent;
+	type DustRemoval = ();
+	type TransferPayment = ();
+	type ExistentialDeposit = ExistentialDeposit;
+	type TransferFee = TransferFee;
+	type CreationFee = CreationFee;
+}
+
+/// The account the mock treasury pays everything it receives into.
+const TREASURY: u64 = 100;
+const ALICE: u64 = 1;
+
+/// Pays whatever it receives into `TREASURY`, like the treasury pallet does into its account.
+pub struct MockTreasury;
+
+impl OnUnbalanced<NegativeImbalanceOf<Test>> for MockTreasury {
+	fn on_unbalanced(amount: NegativeImbalanceOf<Test>) {
+		Balances::resolve_creating(&TREASURY, amount);
+	}
+}
+
+parameter_types! {
+	pub const TargetBlockTime: u64 = 60_000;
+	pub const VoteWindow: u32 = 10;
+	pub const VoteThreshold: Perbill = Perbill::from_percent(60);
+	pub const RewardAdjustment: Perbill = Perbill::from_percent(5);
+}
+
+impl Trait for Test {
+	type Event = TestEvent;
+	type RewardCurrency = Balances;
+	type TargetBlockTime = TargetBlockTime;
+	type ParamsOrigin = system::EnsureRoot<u64>;
+	type VoteWindow = VoteWindow;
+	type VoteThreshold = VoteThreshold;
+	type RewardAdjustment = RewardAdjustment;
+	type Treasury = MockTreasury;
+}
+
+type System = system::Module<Test>;
+type Timestamp = timestamp::Module<Test>;
+type Balances = balances::Module<Test>;
+type PowParams = Module<Test>;
+
+/// Genesis with the given reward and treasury share of it. The treasury
+/// account exists from genesis, so that its share is never too small to pay.
+fn new_test_ext(reward: u64, treasury_reward_share: Perbill) -> sp_io::TestExternalities {
+	let mut storage = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	balances::GenesisConfig::<Test> {
+		balances: vec![(TREASURY, 1000)],
+		vesting: vec![],
+	}.assimilate_storage(&mut storage).unwrap();
+	GenesisConfig::<Test> {
+		difficulty: 5000.into(),
+		reward,
+		halving_interval: 0,
+		max_supply: 0,
+		fee_author_share: Perbill::from_percent(50),
+		fee_treasury_share: Perbill::from_percent(25),
+		treasury_reward_share,
+		mode: DifficultyMode::Manual,
+		current_algorithm: Algorithm::Sha3,
+	}.assimilate_storage(&mut storage).unwrap();
+
+	storage.into()
+}
+
+/// Start block `n`, mined by `author` if it has one, with the given vote.
+fn initialize_block(n: u64, author: Option<u64>, vote: Option<Vote>) {
+	let logs = author.into_iter()
+		.map(|author| DigestItem::PreRuntime(POW_ENGINE_ID, PreDigest { author, vote }.encode()))
+		.collect();
+	System::initialize(&n, &H256::default(), &H256::default(), &Digest { logs }, Default::default());
+}
+
+/// Run block `n` with the given author and vote, and timestamp `now`.
+fn run_block(n: u64, author: Option<u64>, vote: Option<Vote>, now: u64) {
+	initialize_block(n, author, vote);
+	PowParams::on_initialize(n);
+	Timestamp::set_timestamp(now);
+	PowParams::on_finalize(n);
+}
+
+#[test]
+fn issuance_grows_by_one_reward_per_block() {
+	new_test_ext(1000, Perbill::from_percent(10)).execute_with(|| {
+		for n in 1..=5 {
+			let issuance = Balances::total_issuance();
+			run_block(n, Some(ALICE), None, n * 60_000);
+			assert_eq!(Balances::total_issuance(), issuance + 1000);
+		}
+
+		assert_eq!(Balances::free_balance(ALICE), 5 * 900);
+		assert_eq!(Balances::free_balance(TREASURY), 1000 + 5 * 100);
+	});
+}
+
+#[test]
+fn author_reward_leaves_out_the_treasury_share() {
+	new_test_ext(1000, Perbill::from_percent(10)).execute_with(|| {
+		assert_eq!(PowParams::block_reward(1), 1000);
+		assert_eq!(PowParams::author_reward(1), 900);
+
+		run_block(1, Some(ALICE), None, 60_000);
+		assert_eq!(Balances::free_balance(ALICE), 900);
+	});
+}
+
+#[test]
+fn blocks_without_author_issue_nothing() {
+	new_test_ext(1000, Perbill::from_percent(10)).execute_with(|| {
+		let issuance = Balances::total_issuance();
+		run_block(1, None, None, 60_000);
+		assert_eq!(Balances::total_issuance(), issuance);
+	});
+}
+
+#[test]
+fn rewards_too_small_for_a_new_account_issue_nothing() {
+	new_test_ext(100, Perbill::zero()).execute_with(|| {
+		let issuance = Balances::total_issuance();
+		run_block(1, Some(ALICE), None, 60_000);
+
+		assert_eq!(Balances::free_balance(ALICE), 0);
+		assert_eq!(Balances::total_issuance(), issuance);
+	});
+}
+
+#[test]
+fn median_time_past_is_the_median_of_recent_blocks() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		assert_eq!(PowParams::median_time_past(), 0);
+
+		// Out of order timestamps, as miners' clocks allow
+		let timestamps = [10, 30, 20, 50, 40, 70, 60, 90, 80, 110, 100, 130, 120];
+		for (i, now) in timestamps.iter().enumerate() {
+			run_block(i as u64 + 1, Some(ALICE), None, *now);
+		}
+
+		// The last 11 blocks, from 20 to 130, have the median 80
+		assert_eq!(PowParams::recent_timestamps().len(), MEDIAN_TIME_SPAN);
+		assert_eq!(PowParams::median_time_past(), 80);
+	});
+}
+
+#[test]
+fn setting_the_difficulty_keeps_the_median_time_past() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		for n in 1..=11 {
+			run_block(n, Some(ALICE), None, n * 1000);
+		}
+		assert_eq!(PowParams::median_time_past(), 6000);
+
+		assert_ok!(PowParams::set_difficulty(Origin::ROOT, 10_000.into()));
+		assert!(PowParams::past_difficulties_and_timestamps().is_empty());
+		assert_eq!(PowParams::median_time_past(), 6000);
+	});
+}
+
+#[test]
+fn reward_halves_at_each_interval() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		HalvingInterval::<Test>::put(10);
+
+		assert_eq!(PowParams::reward_at(9), 1000);
+		assert_eq!(PowParams::reward_at(10), 500);
+		assert_eq!(PowParams::reward_at(11), 500);
+		assert_eq!(PowParams::reward_at(19), 500);
+		assert_eq!(PowParams::reward_at(20), 250);
+		assert_eq!(PowParams::reward_at(10 * 64), 0);
+	});
+}
+
+#[test]
+fn zero_interval_never_halves() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		assert_eq!(PowParams::reward_at(1), 1000);
+		assert_eq!(PowParams::reward_at(u64::max_value()), 1000);
+		assert_eq!(PowParams::projected_issuance(100), Balances::total_issuance() + 100 * 1000);
+	});
+}
+
+#[test]
+fn issuance_stops_at_max_supply() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		// Room for two and a half rewards above the treasury's genesis balance
+		MaxSupply::<Test>::put(1000 + 2500);
+
+		run_block(1, Some(ALICE), None, 60_000);
+		run_block(2, Some(ALICE), None, 120_000);
+		assert_eq!(PowParams::block_reward(3), 500);
+		run_block(3, Some(ALICE), None, 180_000);
+		assert_eq!(Balances::total_issuance(), 3500);
+
+		assert_eq!(PowParams::block_reward(4), 0);
+		run_block(4, Some(ALICE), None, 240_000);
+		assert_eq!(Balances::total_issuance(), 3500);
+		assert_eq!(Balances::free_balance(ALICE), 2500);
+	});
+}
+
+#[test]
+fn projected_issuance_follows_the_halvings() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		HalvingInterval::<Test>::put(10);
+		let issuance = Balances::total_issuance();
+
+		// Blocks 1 to 9 pay 1000, 10 to 19 pay 500, 20 to 29 pay 250 and 30 to 35 pay 125
+		assert_eq!(PowParams::projected_issuance(35), issuance + 9 * 1000 + 10 * 500 + 10 * 250 + 6 * 125);
+
+		MaxSupply::<Test>::put(issuance + 12_000);
+		assert_eq!(PowParams::projected_issuance(35), issuance + 12_000);
+		assert_eq!(PowParams::projected_issuance(12), issuance + 9 * 1000 + 3 * 500);
+	});
+}
+
+#[test]
+fn projected_issuance_ends_when_the_reward_halves_to_zero() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		HalvingInterval::<Test>::put(1);
+
+		// 500 + 250 + 125 + 62 + 31 + 15 + 7 + 3 + 1
+		assert_eq!(PowParams::projected_issuance(u64::max_value()), Balances::total_issuance() + 994);
+	});
+}
+
+#[test]
+fn projected_issuance_handles_halvings_beyond_the_last_block_number() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		let interval = u64::max_value() / 2 + 1;
+		HalvingInterval::<Test>::put(interval);
+		System::set_block_number(interval + 1);
+
+		// The second halving would be at 2 * interval, which doesn't fit
+		assert_eq!(PowParams::projected_issuance(interval + 10), Balances::total_issuance() + 9 * 500);
+	});
+}
+
+#[test]
+fn projected_issuance_ends_at_the_last_block_number() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		System::set_block_number(u64::max_value() - 3);
+
+		assert_eq!(PowParams::projected_issuance(u64::max_value()), Balances::total_issuance() + 3 * 1000);
+	});
+}
+
+#[test]
+fn projected_issuance_of_past_blocks_is_the_current_issuance() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		for n in 1..=5 {
+			run_block(n, Some(ALICE), None, n * 60_000);
+		}
+		let issuance = Balances::total_issuance();
+
+		assert_eq!(PowParams::projected_issuance(5), issuance);
+		assert_eq!(PowParams::projected_issuance(3), issuance);
+		assert_eq!(PowParams::projected_issuance(6), issuance + 1000);
+	});
+}
+
+#[test]
+fn reward_changes_once_the_threshold_votes() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		// 60% of the 10 block window is 6 votes
+		for n in 1..=5 {
+			run_block(n, Some(ALICE), Some(Vote::IncreaseReward), n * 60_000);
+		}
+		assert_eq!(PowParams::vote_tally(), (5, 0));
+		assert_eq!(PowParams::reward(), 1000);
+
+		run_block(6, Some(ALICE), Some(Vote::IncreaseReward), 360_000);
+		assert_eq!(PowParams::reward(), 1050);
+		assert_eq!(PowParams::last_voted_adjustment(), 6);
+	});
+}
+
+#[test]
+fn votes_leave_the_window() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		for n in 1..=5 {
+			run_block(n, Some(ALICE), Some(Vote::DecreaseReward), n * 60_000);
+		}
+		for n in 6..=10 {
+			run_block(n, Some(ALICE), None, n * 60_000);
+		}
+		// Block 11 takes the slot of block 1, whose vote leaves the window
+		run_block(11, Some(ALICE), Some(Vote::DecreaseReward), 660_000);
+		assert_eq!(PowParams::vote_tally(), (0, 5));
+		run_block(12, Some(ALICE), Some(Vote::IncreaseReward), 720_000);
+		assert_eq!(PowParams::vote_tally(), (1, 4));
+
+		assert_eq!(PowParams::reward(), 1000);
+	});
+}
+
+#[test]
+fn reward_changes_at_most_once_per_window() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		for n in 1..=15 {
+			run_block(n, Some(ALICE), Some(Vote::IncreaseReward), n * 60_000);
+		}
+		assert_eq!(PowParams::reward(), 1050);
+
+		run_block(16, Some(ALICE), Some(Vote::IncreaseReward), 960_000);
+		assert_eq!(PowParams::reward(), 1050 + RewardAdjustment::get() * 1050);
+		assert_eq!(PowParams::last_voted_adjustment(), 16);
+	});
+}
+
+#[test]
+fn small_rewards_change_by_at_least_one() {
+	new_test_ext(5, Perbill::zero()).execute_with(|| {
+		for n in 1..=6 {
+			run_block(n, Some(ALICE), Some(Vote::IncreaseReward), n * 60_000);
+		}
+		assert_eq!(PowParams::reward(), 6);
+	});
+}
+
+#[test]
+fn votes_that_cannot_change_the_reward_are_not_an_adjustment() {
+	new_test_ext(0, Perbill::zero()).execute_with(|| {
+		for n in 1..=6 {
+			run_block(n, Some(ALICE), Some(Vote::DecreaseReward), n * 60_000);
+		}
+		assert_eq!(PowParams::reward(), 0);
+		assert_eq!(PowParams::last_voted_adjustment(), 0);
+
+		// So votes to increase it apply as soon as they reach the threshold
+		for n in 7..=12 {
+			run_block(n, Some(ALICE), Some(Vote::IncreaseReward), n * 60_000);
+		}
+		assert_eq!(PowParams::reward(), 1);
+		assert_eq!(PowParams::last_voted_adjustment(), 12);
+	});
+}
+
+#[test]
+fn fees_are_split_between_author_treasury_and_burning() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		initialize_block(1, Some(ALICE), None);
+		let issuance = Balances::total_issuance();
+
+		// Half to the author, a quarter to the treasury, and the rest burned
+		PowParams::on_unbalanced(Balances::issue(10_000));
+
+		assert_eq!(Balances::free_balance(ALICE), 5000);
+		assert_eq!(Balances::free_balance(TREASURY), 1000 + 2500);
+		assert_eq!(Balances::total_issuance(), issuance + 7500);
+	});
+}
+
+#[test]
+fn fee_shares_too_small_for_a_new_account_are_burned() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		initialize_block(1, Some(ALICE), None);
+		let issuance = Balances::total_issuance();
+
+		// The author's half is below the existential deposit
+		PowParams::on_unbalanced(Balances::issue(800));
+
+		assert_eq!(Balances::free_balance(ALICE), 0);
+		assert_eq!(Balances::free_balance(TREASURY), 1000 + 200);
+		assert_eq!(Balances::total_issuance(), issuance + 200);
+		assert_eq!(
+			System::events().last().map(|record| record.event.clone()),
+			Some(TestEvent::pow_params(RawEvent::FeeDistributed(Some(ALICE), 0, 200, 600))),
+		);
+	});
+}
+
+#[test]
+fn fees_of_blocks_without_author_burn_the_author_share() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		initialize_block(1, None, None);
+		let issuance = Balances::total_issuance();
+
+		PowParams::on_unbalanced(Balances::issue(10_000));
+
+		assert_eq!(Balances::free_balance(TREASURY), 1000 + 2500);
+		assert_eq!(Balances::total_issuance(), issuance + 2500);
+	});
+}
+
+#[test]
+fn fee_shares_can_take_the_whole_fee() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		initialize_block(1, Some(ALICE), None);
+		let issuance = Balances::total_issuance();
+
+		assert_ok!(PowParams::set_fee_shares(Origin::ROOT, Perbill::from_percent(60), Perbill::from_percent(40)));
+		PowParams::on_unbalanced(Balances::issue(10_000));
+
+		assert_eq!(Balances::free_balance(ALICE), 6000);
+		assert_eq!(Balances::free_balance(TREASURY), 1000 + 4000);
+		assert_eq!(Balances::total_issuance(), issuance + 10_000);
+	});
+}
+
+#[test]
+fn fee_shares_must_not_exceed_the_whole_fee() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		assert_noop!(
+			PowParams::set_fee_shares(Origin::ROOT, Perbill::from_percent(60), Perbill::from_percent(41)),
+			"Fee shares must not add up to more than the whole fee"
+		);
+		assert!(PowParams::set_fee_shares(Origin::signed(ALICE), Perbill::zero(), Perbill::zero()).is_err());
+
+		assert_eq!(PowParams::fee_author_share(), Perbill::from_percent(50));
+		assert_eq!(PowParams::fee_treasury_share(), Perbill::from_percent(25));
+	});
+}
+
+/// `len` blocks of the given difficulty, mined `spacing` milliseconds apart.
+fn window(difficulty: u64, spacing: u64, len: u64) -> Vec<DifficultyAndTimestamp> {
+	(0..len)
+		.map(|i| DifficultyAndTimestamp { difficulty: difficulty.into(), timestamp: i * spacing })
+		.collect()
+}
+
+#[test]
+fn on_target_blocks_keep_the_difficulty() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		assert_ok!(PowParams::set_difficulty_mode(Origin::ROOT, DifficultyMode::Automatic));
+
+		// Through the partially filled window and past the full one
+		for n in 1..=DIFFICULTY_ADJUST_WINDOW + 10 {
+			run_block(n, Some(ALICE), None, n * 60_000);
+			assert_eq!(PowParams::difficulty(), 5000.into());
+		}
+		assert_eq!(PowParams::past_difficulties_and_timestamps().len(), DIFFICULTY_ADJUST_WINDOW as usize);
+	});
+}
+
+#[test]
+fn fast_blocks_raise_the_difficulty_by_at_most_half() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		// The damping keeps even instant blocks above the lower clamp
+		let full = window(10_000, 0, DIFFICULTY_ADJUST_WINDOW);
+		assert_eq!(PowParams::next_difficulty(&full), 15_000.into());
+	});
+}
+
+#[test]
+fn slow_blocks_halve_the_difficulty_at_most() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		let full = window(10_000, 10 * 60_000, DIFFICULTY_ADJUST_WINDOW);
+		assert_eq!(PowParams::next_difficulty(&full), 5000.into());
+
+		let slower = window(10_000, 100 * 60_000, DIFFICULTY_ADJUST_WINDOW);
+		assert_eq!(PowParams::next_difficulty(&slower), 5000.into());
+	});
+}
+
+#[test]
+fn missing_blocks_of_a_partial_window_are_on_target() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		Difficulty::put(U256::from(10_000));
+
+		// 30 instant blocks and 30 assumed to take 60s each: 1800s of 3540s, damped to 2960s
+		let partial = window(10_000, 0, DIFFICULTY_ADJUST_WINDOW / 2);
+		assert_eq!(PowParams::next_difficulty(&partial), (10_000u64 * 3540 / 2960).into());
+	});
+}
+
+#[test]
+fn retargeting_never_goes_below_the_minimum_difficulty() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		let slow = window(1500, 10 * 60_000, DIFFICULTY_ADJUST_WINDOW);
+		assert_eq!(PowParams::next_difficulty(&slow), MIN_DIFFICULTY.into());
+	});
+}
+
+#[test]
+fn manual_mode_leaves_the_difficulty_alone() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		for n in 1..=5 {
+			run_block(n, Some(ALICE), None, n);
+		}
+		assert_eq!(PowParams::difficulty(), 5000.into());
+		assert_eq!(PowParams::past_difficulties_and_timestamps().len(), 5);
+	});
+}
+
+#[test]
+fn setting_the_difficulty_restarts_the_window() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		assert_ok!(PowParams::set_difficulty_mode(Origin::ROOT, DifficultyMode::Automatic));
+		for n in 1..=10 {
+			run_block(n, Some(ALICE), None, n);
+		}
+		assert!(PowParams::difficulty() > 5000.into());
+
+		assert_ok!(PowParams::set_difficulty(Origin::ROOT, 8000.into()));
+		assert!(PowParams::past_difficulties_and_timestamps().is_empty());
+
+		// The fast blocks before the reset no longer count
+		run_block(11, Some(ALICE), None, 11);
+		assert_eq!(PowParams::difficulty(), 8000.into());
+		assert_eq!(PowParams::past_difficulties_and_timestamps().len(), 1);
+	});
+}
+
+#[test]
+fn algorithm_switches_are_scheduled_for_future_blocks() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		initialize_block(1, None, None);
+
+		assert_noop!(
+			PowParams::schedule_algorithm_switch(Origin::ROOT, 1, Algorithm::Blake2b),
+			"Algorithm switch must be scheduled for a future block"
+		);
+		assert!(PowParams::schedule_algorithm_switch(Origin::signed(ALICE), 5, Algorithm::Blake2b).is_err());
+
+		assert_ok!(PowParams::schedule_algorithm_switch(Origin::ROOT, 5, Algorithm::Blake2b));
+		assert_eq!(PowParams::scheduled_algorithm(), Some((5, Algorithm::Blake2b)));
+
+		// A new switch replaces the old one
+		assert_ok!(PowParams::schedule_algorithm_switch(Origin::ROOT, 8, Algorithm::Keccak));
+		assert_eq!(PowParams::scheduled_algorithm(), Some((8, Algorithm::Keccak)));
+		assert_eq!(PowParams::current_algorithm(), Algorithm::Sha3);
+	});
+}
+
+#[test]
+fn algorithm_switches_can_be_cancelled() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		initialize_block(1, None, None);
+
+		assert_noop!(PowParams::cancel_algorithm_switch(Origin::ROOT), "No algorithm switch is scheduled");
+
+		assert_ok!(PowParams::schedule_algorithm_switch(Origin::ROOT, 5, Algorithm::Blake2b));
+		assert!(PowParams::cancel_algorithm_switch(Origin::signed(ALICE)).is_err());
+		assert_ok!(PowParams::cancel_algorithm_switch(Origin::ROOT));
+		assert_eq!(PowParams::scheduled_algorithm(), None);
+
+		for n in 2..=6 {
+			run_block(n, Some(ALICE), None, n * 60_000);
+		}
+		assert_eq!(PowParams::current_algorithm(), Algorithm::Sha3);
+	});
+}
+
+#[test]
+fn algorithm_switches_apply_at_the_scheduled_block() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		initialize_block(1, None, None);
+		assert_ok!(PowParams::schedule_algorithm_switch(Origin::ROOT, 5, Algorithm::Blake2b));
+
+		for n in 2..=4 {
+			run_block(n, Some(ALICE), None, n * 60_000);
+			assert_eq!(PowParams::current_algorithm(), Algorithm::Sha3);
+		}
+
+		run_block(5, Some(ALICE), None, 5 * 60_000);
+		assert_eq!(PowParams::current_algorithm(), Algorithm::Blake2b);
+		assert_eq!(PowParams::scheduled_algorithm(), None);
+	});
+}
+
+#[test]
+fn next_algorithm_switches_one_block_early() {
+	new_test_ext(1000, Perbill::zero()).execute_with(|| {
+		initialize_block(1, None, None);
+		assert_ok!(PowParams::schedule_algorithm_switch(Origin::ROOT, 5, Algorithm::Blake2b));
+
+		run_block(3, Some(ALICE), None, 3 * 60_000);
+		assert_eq!(PowParams::next_algorithm(), Algorithm::Sha3);
+
+		// Block 5 is built on block 4, whose state must already report the new algorithm
+		run_block(4, Some(ALICE), None, 4 * 60_000);
+		assert_eq!(PowParams::current_algorithm(), Algorithm::Sha3);
+		assert_eq!(PowParams::next_algorithm(), Algorithm::Blake2b);
+	});
+}
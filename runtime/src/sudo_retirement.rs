@@ -0,0 +1,56 @@
+/// A Pallet that lets governance retire the Sudo key.
+///
+/// The sudo key holds root until a network's governance works. A referendum
+/// then calls `retire_sudo`, which hands the key to an account nobody controls,
+/// and root is only reachable through referenda from then on. Sudo stays in the
+/// runtime, so that no module after it moves in the call and event indices.
+
+use frame_support::{decl_module, decl_event, dispatch::{DispatchResult, Dispatchable}, traits::EnsureOrigin};
+use sp_runtime::{ModuleId, traits::{AccountIdConversion, StaticLookup}};
+
+/// Derives the account the retired key is handed to. Like module accounts, it
+/// has no secret key.
+const RETIRED_KEY_ID: ModuleId = ModuleId(*b"cle/sudo");
+
+pub trait Trait: sudo::Trait {
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+	/// The origin allowed to retire the sudo key.
+	type RetireOrigin: EnsureOrigin<Self::Origin>;
+}
+
+decl_module! {
+	/// The module declaration.
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		fn deposit_event() = default;
+
+		/// Hand the sudo key to an account nobody controls.
+		pub fn retire_sudo(origin) -> DispatchResult {
+			T::RetireOrigin::ensure_origin(origin)?;
+
+			// Sudo only takes a new key from the current one.
+			let key = <sudo::Module<T>>::key();
+			let retired = Self::retired_key();
+			sudo::Call::<T>::set_key(T::Lookup::unlookup(retired.clone()))
+				.dispatch(system::RawOrigin::Signed(key).into())?;
+
+			Self::deposit_event(RawEvent::SudoRetired(retired));
+			Ok(())
+		}
+	}
+}
+
+decl_event!(
+	pub enum Event<T> where
+		AccountId = <T as system::Trait>::AccountId,
+	{
+		/// The sudo key has been handed to the given account, which nobody controls
+		SudoRetired(AccountId),
+	}
+);
+
+impl<T: Trait> Module<T> {
+	/// The account the sudo key is handed to when it is retired.
+	pub fn retired_key() -> T::AccountId {
+		RETIRED_KEY_ID.into_account()
+	}
+}
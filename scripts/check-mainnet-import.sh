@@ -0,0 +1,32 @@
+#!/usr/bin/env bash
+#
+# Checks that this node can import the live chain's blocks. Export them with a
+# node that already follows mainnet, e.g. a release from before this one:
+#
+#   cle-coin-old export-blocks --chain=mainnet-spec.json --from 1 --to 1000 mainnet-blocks.bin
+#
+# Usage: ./scripts/check-mainnet-import.sh mainnet-blocks.bin [LAST_BLOCK] [path/to/cle-coin]
+#
+# The blocks are imported into a fresh database, then exported again. The check
+# passes if every block imported and the export matches the input exactly.
+
+set -e
+
+BLOCKS=${1:?"Usage: $0 BLOCKS_FILE [LAST_BLOCK] [NODE]"}
+LAST_BLOCK=${2:-1000}
+NODE=${3:-./target/release/cle-coin}
+BASE_PATH=$(mktemp -d)
+trap 'rm -rf "$BASE_PATH"' EXIT
+
+echo "*** Importing blocks 1 to $LAST_BLOCK from $BLOCKS"
+"$NODE" import-blocks --chain=mainnet --base-path="$BASE_PATH" "$BLOCKS"
+
+echo "*** Exporting the imported blocks"
+"$NODE" export-blocks --chain=mainnet --base-path="$BASE_PATH" --from 1 --to "$LAST_BLOCK" "$BASE_PATH/exported.bin"
+
+if cmp -s "$BLOCKS" "$BASE_PATH/exported.bin" ; then
+   echo "*** All $LAST_BLOCK blocks imported"
+else
+   echo "*** The imported chain differs from $BLOCKS" >&2
+   exit 1
+fi
@@ -0,0 +1,95 @@
+#!/usr/bin/env python3
+"""
+A minimal remote miner for exercising the mining_getWork / mining_submitWork
+RPCs end to end. It is far too slow for real mining; run the node with a low
+difficulty, e.g. `cle-coin --dev --validator --author <ADDRESS> --threads 0`.
+
+Usage: ./scripts/test-miner.py [http://localhost:9933]
+"""
+
+import hashlib
+import json
+import sys
+import time
+import urllib.request
+
+RPC_URL = sys.argv[1] if len(sys.argv) > 1 else "http://localhost:9933"
+
+BASE58_ALPHABET = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz"
+
+
+def rpc(method, params):
+    body = json.dumps({"jsonrpc": "2.0", "id": 1, "method": method, "params": params}).encode()
+    request = urllib.request.Request(RPC_URL, body, {"Content-Type": "application/json"})
+    with urllib.request.urlopen(request) as response:
+        reply = json.load(response)
+    if "error" in reply:
+        raise RuntimeError(reply["error"])
+    return reply["result"]
+
+
+def encode_u256(value):
+    # SCALE encodes U256 as 32 little endian bytes
+    return value.to_bytes(32, "little")
+
+
+def decode_ss58(address):
+    # A one byte address format, the 32 byte account id, then a two byte checksum
+    value = 0
+    for char in address:
+        value = value * 58 + BASE58_ALPHABET.index(char)
+    return value.to_bytes(35, "big")[1:33]
+
+
+# Votes are encoded as their one byte variant index, and left out when there is none
+VOTES = {
+    None: b"",
+    "increaseReward": b"\x00",
+    "decreaseReward": b"\x01",
+}
+
+HASHERS = {
+    "sha3": lambda data: hashlib.sha3_256(data).digest(),
+    "blake2b": lambda data: hashlib.blake2b(data, digest_size=32).digest(),
+}
+
+
+def mine(work, attempts=100000):
+    if work["algorithm"] not in HASHERS:
+        raise RuntimeError("The test miner does not support {}".format(work["algorithm"]))
+
+    hasher = HASHERS[work["algorithm"]]
+    pre_hash = bytes.fromhex(work["preHash"][2:])
+    difficulty = int(work["difficulty"], 16)
+    # Seals name the author and vote only where the chain requires it
+    author = b""
+    if work.get("bindAuthor"):
+        author = decode_ss58(work["author"]) + VOTES[work.get("vote")]
+
+    for counter in range(attempts):
+        nonce = counter.to_bytes(32, "big")
+        # Encoding of the node's `Compute` struct
+        work_hash = hasher(encode_u256(difficulty) + pre_hash + nonce + author)
+        if int.from_bytes(work_hash, "big") * difficulty < 2 ** 256:
+            # Encoding of the node's `Seal` struct
+            return encode_u256(difficulty) + work_hash + nonce + author
+    return None
+
+
+def main():
+    while True:
+        work = rpc("mining_getWork", [])
+        if work is None:
+            time.sleep(1)
+            continue
+
+        seal = mine(work)
+        if seal is None:
+            continue
+
+        accepted = rpc("mining_submitWork", [work["preHash"], "0x" + seal.hex()])
+        print("Submitted seal for {}: {}".format(work["preHash"], "accepted" if accepted else "rejected"))
+
+
+if __name__ == "__main__":
+    main()
@@ -1,16 +1,26 @@
-use sp_core::{Pair, Public, sr25519};
+use sp_core::{Pair, Public, U256, sr25519, crypto::{Ss58Codec, Ss58AddressFormat}};
 use runtime::{
-	AccountId, BalancesConfig, GenesisConfig,
+	AccountId, Balance, BalancesConfig, BlockNumber, GenesisConfig, CLE, DAYS,
 	SudoConfig, IndicesConfig, SystemConfig, WASM_BINARY, Signature, PowParamsConfig,
+	DifficultyMode, GrandpaConfig, CouncilConfig, DemocracyConfig, Perbill,
 };
+use grandpa_primitives::AuthorityId as GrandpaId;
 use sc_service;
-use sp_runtime::traits::{Verify, IdentifyAccount};
+use sp_runtime::{MultiSigner, traits::{Verify, IdentifyAccount}};
 use serde_json::json;
 use sc_telemetry::TelemetryEndpoints;
+use crate::pow::{Algorithm, SS58_PREFIX};
+use crate::finality::{FinalityMode, DEFAULT_CONFIRMATION_DEPTH};
+use crate::import::Checkpoint;
 
 // Note this is the URL for the telemetry server
 const STAGING_TELEMETRY_URL: &str = "wss://telemetry.polkadot.io/submit/";
 
+/// The SS58 address format of CLE Coin accounts.
+pub fn ss58_format() -> Ss58AddressFormat {
+	Ss58AddressFormat::Custom(SS58_PREFIX)
+}
+
 /// Specialized `ChainSpec`. This is a specialization of the general Substrate ChainSpec type.
 pub type ChainSpec = sc_service::ChainSpec<GenesisConfig>;
 
@@ -23,6 +33,11 @@ pub enum Alternative {
 	Development,
 	/// Whatever the current runtime is, with simple Alice/Bob auths.
 	LocalTestnet,
+	/// A public testnet running the current runtime, for trying out upgrades
+	/// before they reach mainnet.
+	StagingTestnet,
+	/// The live CLE Coin network, as launched at the meetup.
+	Mainnet,
 }
 
 /// Helper function to generate a crypto pair from seed
@@ -41,6 +56,15 @@ pub fn get_account_id_from_seed<TPublic: Public>(seed: &str) -> AccountId where
 	AccountPublic::from(get_from_seed::<TPublic>(seed)).into_account()
 }
 
+/// Parse an SS58 encoded sr25519 public key into the runtime's `AccountId`.
+/// Addresses with any prefix are accepted, so that accounts created with
+/// generic Substrate tools keep working.
+pub fn parse_account(address: &str) -> Result<AccountId, String> {
+	sr25519::Public::from_ss58check_with_version(address)
+		.map(|(public, _)| MultiSigner::from(public).into_account())
+		.map_err(|e| format!("Invalid account address {}: {:?}", address, e))
+}
+
 impl Alternative {
 	/// Get an actual chain config from one of the alternatives.
 	pub(crate) fn load(self) -> Result<ChainSpec, String> {
@@ -56,56 +80,59 @@ impl Alternative {
 					get_account_id_from_seed::<sr25519::Public>("Alice//stash"),
 					get_account_id_from_seed::<sr25519::Public>("Bob//stash"),
 				],
+				vec![
+					get_from_seed::<GrandpaId>("Alice"),
+				],
 				true),
 				vec![],
 				None,
 				None,
-				None,
+				Some(testnet_properties()),
 				None
 			),
 			Alternative::LocalTestnet => ChainSpec::from_genesis(
+				"Local Testnet",
+				"local_testnet",
+				|| testnet_genesis(
+				get_account_id_from_seed::<sr25519::Public>("Alice"),
+				testnet_endowed_accounts(),
+				// Initial GRANDPA voters, only used with GRANDPA finality
+				vec![],
+				true),
+				vec![],
+				None,
+				None,
+				Some(testnet_properties()),
+				None
+			),
+			Alternative::StagingTestnet => ChainSpec::from_genesis(
 				// Name
-				"CLE Coin Mainnet",
+				"CLE Coin Staging Testnet",
 				// id
-				"cle_coin",
+				"cle_coin_staging",
 				|| testnet_genesis(
 				// Root Key
 				get_account_id_from_seed::<sr25519::Public>("Alice"),
 				// Endowed Accounts
-				vec![
-					get_account_id_from_seed::<sr25519::Public>("Alice"),
-					get_account_id_from_seed::<sr25519::Public>("Bob"),
-					get_account_id_from_seed::<sr25519::Public>("Charlie"),
-					get_account_id_from_seed::<sr25519::Public>("Dave"),
-					get_account_id_from_seed::<sr25519::Public>("Eve"),
-					get_account_id_from_seed::<sr25519::Public>("Ferdie"),
-					get_account_id_from_seed::<sr25519::Public>("Alice//stash"),
-					get_account_id_from_seed::<sr25519::Public>("Bob//stash"),
-					get_account_id_from_seed::<sr25519::Public>("Charlie//stash"),
-					get_account_id_from_seed::<sr25519::Public>("Dave//stash"),
-					get_account_id_from_seed::<sr25519::Public>("Eve//stash"),
-					get_account_id_from_seed::<sr25519::Public>("Ferdie//stash"),
-				],
+				testnet_endowed_accounts(),
+				// Initial GRANDPA voters, only used with GRANDPA finality
+				vec![],
 				// Enable Println
 				true),
 				// Bootnodes
-				vec!["/dns4/cleveland.bootnodes.net/tcp/30333/p2p/QmPQwJBLMZTsJxFC4AFEGAiXSN9R49aJ8B2UNE6FaLdhFd".to_string()],
+				vec![],
 				// Telemetry Endpoints
 				Some(TelemetryEndpoints::new(vec![
 					(STAGING_TELEMETRY_URL.to_string(), 1),
 				])),
 				// Protocol ID
-				Some("cle"),
+				Some("cle-staging"),
 				// Properties
-				// This seems to not be working. https://substrate.dev/rustdocs/pre-v2.0-3e65111/sc_chain_spec/type.Properties.html
-				// I copied it from kulupu
-				Some(json!({
-					"tokenDecimals": 12,
-					"tokenSymbol": "CLE"
-				}).as_object().expect("Created an object").clone()),
+				Some(testnet_properties()),
 				// Extensions
 				None
 			),
+			Alternative::Mainnet => ChainSpec::from_json_bytes(&include_bytes!("../mainnet-spec.json")[..])?,
 		})
 	}
 
@@ -114,33 +141,176 @@ impl Alternative {
 			"dev" => Some(Alternative::Development),
 			// Empty string means local testnet. It's our default.
 			"" | "local" => Some(Alternative::LocalTestnet),
+			"staging" => Some(Alternative::StagingTestnet),
+			"mainnet" => Some(Alternative::Mainnet),
 			_ => None,
 		}
 	}
 }
 
+/// Accounts endowed by the local and staging testnets.
+fn testnet_endowed_accounts() -> Vec<AccountId> {
+	vec![
+		get_account_id_from_seed::<sr25519::Public>("Alice"),
+		get_account_id_from_seed::<sr25519::Public>("Bob"),
+		get_account_id_from_seed::<sr25519::Public>("Charlie"),
+		get_account_id_from_seed::<sr25519::Public>("Dave"),
+		get_account_id_from_seed::<sr25519::Public>("Eve"),
+		get_account_id_from_seed::<sr25519::Public>("Ferdie"),
+		get_account_id_from_seed::<sr25519::Public>("Alice//stash"),
+		get_account_id_from_seed::<sr25519::Public>("Bob//stash"),
+		get_account_id_from_seed::<sr25519::Public>("Charlie//stash"),
+		get_account_id_from_seed::<sr25519::Public>("Dave//stash"),
+		get_account_id_from_seed::<sr25519::Public>("Eve//stash"),
+		get_account_id_from_seed::<sr25519::Public>("Ferdie//stash"),
+	]
+}
+
+/// Chain spec properties of the local and staging testnets.
+fn testnet_properties() -> sc_service::Properties {
+	json!({
+		"tokenDecimals": 12,
+		"tokenSymbol": "CLE",
+		"ss58Format": SS58_PREFIX,
+		"powAlgorithm": Algorithm::Sha3,
+		"finality": FinalityMode::Confirmations,
+		"confirmationDepth": DEFAULT_CONFIRMATION_DEPTH,
+		"authorRequiredFrom": 0,
+	}).as_object().expect("Created an object").clone()
+}
+
+/// The PoW algorithm named by the chain spec's `powAlgorithm` property, one of
+/// `sha3`, `blake2b`, `keccak` or `argon2`. Chains without the property use Sha3.
+///
+/// This only applies to blocks whose runtime doesn't report an algorithm
+/// through `AlgorithmApi`.
+pub fn pow_algorithm(spec: &ChainSpec) -> Result<Algorithm, String> {
+	match spec.properties().get("powAlgorithm") {
+		Some(name) => serde_json::from_value(name.clone())
+			.map_err(|e| format!("Invalid powAlgorithm {} in chain spec: {}", name, e)),
+		None => Ok(Algorithm::default()),
+	}
+}
+
+/// The number of confirmations after which full nodes finalize a block, as set
+/// by the chain spec's `confirmationDepth` property.
+pub fn confirmation_depth(spec: &ChainSpec) -> Result<u32, String> {
+	match spec.properties().get("confirmationDepth") {
+		Some(depth) => serde_json::from_value(depth.clone())
+			.map_err(|e| format!("Invalid confirmationDepth {} in chain spec: {}", depth, e)),
+		None => Ok(DEFAULT_CONFIRMATION_DEPTH),
+	}
+}
+
+/// How the chain is finalized, as set by the chain spec's `finality` property:
+/// `confirmations` (the default) or `grandpa`.
+pub fn finality_mode(spec: &ChainSpec) -> Result<FinalityMode, String> {
+	match spec.properties().get("finality") {
+		Some(mode) => serde_json::from_value(mode.clone())
+			.map_err(|e| format!("Invalid finality {} in chain spec: {}", mode, e)),
+		None => Ok(FinalityMode::Confirmations),
+	}
+}
+
+/// The first block that must name its author in its seal, as set by the chain
+/// spec's `authorRequiredFrom` property. Earlier blocks may carry the original
+/// seals without an author. Chains without the property never require one,
+/// because their history predates seal authors.
+pub fn author_required_from(spec: &ChainSpec) -> Result<Option<u32>, String> {
+	match spec.properties().get("authorRequiredFrom") {
+		Some(number) => serde_json::from_value(number.clone())
+			.map(Some)
+			.map_err(|e| format!("Invalid authorRequiredFrom {} in chain spec: {}", number, e)),
+		None => Ok(None),
+	}
+}
+
+/// The blocks listed in the chain spec's `checkpoints` property, each given as
+/// `{ "number": ..., "hash": "0x..." }`. Nodes refuse to import a fork that
+/// disagrees with any of them.
+pub fn checkpoints(spec: &ChainSpec) -> Result<Vec<Checkpoint>, String> {
+	match spec.properties().get("checkpoints") {
+		Some(checkpoints) => serde_json::from_value(checkpoints.clone())
+			.map_err(|e| format!("Invalid checkpoints in chain spec: {}", e)),
+		None => Ok(Vec::new()),
+	}
+}
+
 fn testnet_genesis(
 	root_key: AccountId,
 	endowed_accounts: Vec<AccountId>,
+	initial_voters: Vec<GrandpaId>,
 	_enable_println: bool) -> GenesisConfig {
+	genesis_config(
+		root_key,
+		endowed_accounts.into_iter().map(|k| (k, 1 << 60)).collect(),
+		initial_voters,
+		vec![
+			get_account_id_from_seed::<sr25519::Public>("Alice"),
+			get_account_id_from_seed::<sr25519::Public>("Bob"),
+			get_account_id_from_seed::<sr25519::Public>("Charlie"),
+		],
+		5000.into(),
+		// Well above the existential deposit, so that the treasury's share
+		// of the reward is enough to create its account
+		CLE,
+		// Halve the reward every year, without a supply cap
+		365 * DAYS,
+		0,
+		Algorithm::Sha3,
+	)
+}
+
+/// The genesis of a chain with the given sudo key, endowments, GRANDPA voters,
+/// council and PoW parameters. Difficulty is retargeted automatically from the
+/// start. A zero `halving_interval` never halves the reward, and a zero
+/// `max_supply` doesn't cap it. A tenth of each block reward goes to the
+/// treasury. Half of each transaction fee goes to the block author, a quarter
+/// to the treasury, and the rest is burned.
+pub fn genesis_config(
+	root_key: AccountId,
+	endowments: Vec<(AccountId, Balance)>,
+	initial_voters: Vec<GrandpaId>,
+	council: Vec<AccountId>,
+	difficulty: U256,
+	reward: Balance,
+	halving_interval: BlockNumber,
+	max_supply: Balance,
+	algorithm: Algorithm,
+) -> GenesisConfig {
 	GenesisConfig {
 		system: Some(SystemConfig {
 			code: WASM_BINARY.to_vec(),
 			changes_trie_config: Default::default(),
 		}),
 		indices: Some(IndicesConfig {
-			ids: endowed_accounts.clone(),
+			ids: endowments.iter().map(|(k, _)| k.clone()).collect(),
 		}),
 		balances: Some(BalancesConfig {
-			balances: endowed_accounts.iter().cloned().map(|k|(k, 1 << 60)).collect(),
+			balances: endowments,
 			vesting: vec![],
 		}),
 		sudo: Some(SudoConfig {
 			key: root_key,
 		}),
 		pow_params: Some(PowParamsConfig {
-			difficulty: 5000.into(),
-			reward: 100,
+			difficulty,
+			reward,
+			halving_interval,
+			max_supply,
+			fee_author_share: Perbill::from_percent(50),
+			fee_treasury_share: Perbill::from_percent(25),
+			treasury_reward_share: Perbill::from_percent(10),
+			mode: DifficultyMode::Automatic,
+			current_algorithm: algorithm,
+		}),
+		grandpa: Some(GrandpaConfig {
+			authorities: initial_voters.iter().map(|x| (x.clone(), 1)).collect(),
+		}),
+		collective_Instance1: Some(CouncilConfig {
+			members: council,
+			phantom: Default::default(),
 		}),
+		democracy: Some(DemocracyConfig::default()),
 	}
 }
@@ -1,12 +1,89 @@
 use crate::service;
-use futures::{future::{select, Map}, FutureExt, TryFutureExt, channel::oneshot, compat::Future01CompatExt};
+use crate::pow::{MiningStats, Vote};
+use futures::{
+	future::{self, select, Map}, FutureExt, TryFutureExt, TryStreamExt, channel::oneshot,
+	compat::{Future01CompatExt, Stream01CompatExt},
+};
 use std::cell::RefCell;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::runtime::Runtime;
 pub use sc_cli::{VersionInfo, IntoExit, error};
-use sc_cli::{display_role, informant, parse_and_prepare, ParseAndPrepare, NoCustom};
+use sc_cli::{display_role, informant, parse_and_prepare, ParseAndPrepare, NoCustom, GetSharedParams, SharedParams};
 use sc_service::{AbstractService, Roles as ServiceRoles, Configuration};
 use crate::chain_spec;
+use crate::genesis::GenesisCmd;
+use crate::key::KeyCmd;
+use crate::tx::TxCmd;
 use log::info;
+use structopt::StructOpt;
+
+/// Additional arguments accepted by the `run` command.
+#[derive(Debug, StructOpt, Clone)]
+pub struct CustomArgs {
+	/// SS58 address of the account that receives the reward for blocks mined by this node.
+	/// Defaults to the mining key in the node's keystore.
+	#[structopt(long)]
+	author: Option<String>,
+
+	/// Number of threads to mine with. Use 0 to leave mining to remote miners.
+	#[structopt(long, default_value = "1")]
+	threads: usize,
+
+	/// Reject blocks that fork off more than this many blocks below the best block.
+	#[structopt(long)]
+	max_reorg_depth: Option<u32>,
+
+	/// Vote in every block this node mines: `increase-reward` or `decrease-reward`.
+	#[structopt(long, parse(try_from_str = parse_vote))]
+	vote: Option<Vote>,
+}
+
+fn parse_vote(vote: &str) -> Result<Vote, String> {
+	match vote {
+		"increase-reward" => Ok(Vote::IncreaseReward),
+		"decrease-reward" => Ok(Vote::DecreaseReward),
+		_ => Err(format!("Unknown vote {}. Use increase-reward or decrease-reward.", vote)),
+	}
+}
+
+impl sc_cli::AugmentClap for CustomArgs {
+	fn augment_clap<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
+		<CustomArgs as StructOpt>::augment_clap(app)
+	}
+}
+
+/// Subcommands that CLE Coin adds to the standard ones.
+#[derive(Debug, StructOpt, Clone)]
+pub enum Subcommand {
+	/// Build a raw chain spec from a genesis config file.
+	#[structopt(name = "genesis")]
+	Genesis(GenesisCmd),
+
+	/// Generate, inspect and insert keys.
+	#[structopt(name = "key")]
+	Key(KeyCmd),
+
+	/// Build and sign transactions offline.
+	#[structopt(name = "tx")]
+	Tx(TxCmd),
+}
+
+impl GetSharedParams for Subcommand {
+	fn shared_params(&self) -> Option<&SharedParams> {
+		None
+	}
+}
+
+impl Subcommand {
+	fn run(self, version: &VersionInfo) -> error::Result<()> {
+		match self {
+			Subcommand::Genesis(cmd) => cmd.run(),
+			Subcommand::Key(cmd) => cmd.run(version),
+			Subcommand::Tx(cmd) => cmd.run(),
+		}
+	}
+}
 
 /// Parse command line arguments into service configuration.
 pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()> where
@@ -15,26 +92,33 @@ pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()>
 	E: IntoExit,
 {
 	type Config<T> = Configuration<(), T>;
-	match parse_and_prepare::<NoCustom, NoCustom, _>(&version, "substrate-node", args) {
+	match parse_and_prepare::<Subcommand, CustomArgs, _>(&version, "substrate-node", args) {
 		ParseAndPrepare::Run(cmd) => cmd.run(load_spec, exit,
-		|exit, _cli_args, _custom_args, config: Config<_>| {
+		|exit, _cli_args, custom_args, config: Config<_>| {
 			info!("{}", version.name);
 			info!("  version {}", config.full_version());
 			info!("  by {}, 2017, 2018", version.author);
 			info!("Chain specification: {}", config.chain_spec.name());
 			info!("Node name: {}", config.name);
 			info!("Roles: {}", display_role(&config));
+			let author = custom_args.author.as_ref().map(|a| chain_spec::parse_account(a)).transpose()?;
+			let mining_stats = Arc::new(MiningStats::default());
+			let is_authority = config.roles.is_authority();
 			let runtime = Runtime::new().map_err(|e| format!("{:?}", e))?;
 			match config.roles {
 				ServiceRoles::LIGHT => run_until_exit(
 					runtime,
 					service::new_light(config)?,
-					exit
+					exit,
+					None,
 				),
 				_ => run_until_exit(
 					runtime,
-					service::new_full(config)?,
-					exit
+					service::new_full(
+						config, author, custom_args.vote, custom_args.threads, mining_stats.clone(), custom_args.max_reorg_depth,
+					)?,
+					exit,
+					if is_authority { Some(mining_stats) } else { None },
 				),
 			}
 		}),
@@ -48,23 +132,44 @@ pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()>
 		ParseAndPrepare::PurgeChain(cmd) => cmd.run(load_spec),
 		ParseAndPrepare::RevertChain(cmd) => cmd.run_with_builder(|config: Config<_>|
 			Ok(new_full_start!(config).0), load_spec),
-		ParseAndPrepare::CustomCommand(_) => Ok(())
+		ParseAndPrepare::CustomCommand(cmd) => cmd.run(&version),
 	}?;
 
 	Ok(())
 }
 
-fn load_spec(id: &str) -> Result<Option<chain_spec::ChainSpec>, String> {
+pub(crate) fn load_spec(id: &str) -> Result<Option<chain_spec::ChainSpec>, String> {
 	Ok(match chain_spec::Alternative::from(id) {
 		Some(spec) => Some(spec.load()?),
 		None => None,
 	})
 }
 
+/// How often the mining informant reports the miner's progress.
+const MINING_INFORMANT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Periodically log the miner's hashrate, blocks found and difficulty.
+fn mining_informant(stats: Arc<MiningStats>) -> impl futures::Future<Output = ()> {
+	tokio::timer::Interval::new_interval(MINING_INFORMANT_INTERVAL)
+		.compat()
+		.try_for_each(move |_| {
+			let snapshot = stats.snapshot();
+			info!(
+				"⛏  Mining at {} H/s, difficulty {}, {} blocks found",
+				snapshot.hashrate,
+				snapshot.difficulty,
+				snapshot.blocks_found,
+			);
+			future::ready(Ok(()))
+		})
+		.map(drop)
+}
+
 fn run_until_exit<T, E>(
 	mut runtime: Runtime,
 	service: T,
 	e: E,
+	mining_stats: Option<Arc<MiningStats>>,
 ) -> error::Result<()>
 where
 	T: AbstractService,
@@ -72,7 +177,12 @@ where
 {
 	let (exit_send, exit) = oneshot::channel();
 
-	let informant = informant::build(&service);
+	let informant = match mining_stats {
+		Some(stats) => future::join(informant::build(&service), mining_informant(stats))
+			.map(drop)
+			.boxed(),
+		None => informant::build(&service).boxed(),
+	};
 
 	let future = select(exit, informant)
 		.map(|_| Ok(()))
@@ -0,0 +1,78 @@
+//! Finality for a PoW chain.
+//!
+//! Chains choose between two modes. By default blocks are finalized
+//! probabilistically, by confirmation depth. Alternatively a GRANDPA voter set
+//! stored in the runtime finalizes the blocks that PoW produces.
+//!
+//! In confirmation mode a block is finalized once it is buried `depth` blocks
+//! below the best block. Reorganising a finalized block would take more work
+//! than the network does in `depth` blocks, and the node refuses to do so once
+//! it is finalized.
+//!
+//! Finalizing blocks lets clients use the usual finality APIs, such as the
+//! `chain_subscribeFinalizedHeads` RPC, to wait for a payment to settle.
+
+use std::sync::Arc;
+use serde::{Serialize, Deserialize};
+use futures::{future, Future, StreamExt};
+use log::{debug, warn};
+use sp_core::Blake2Hasher;
+use sp_runtime::generic::BlockId;
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT, NumberFor, Saturating};
+use sp_blockchain::HeaderBackend;
+use sc_client_api::{BlockchainEvents, Finalizer, backend::Backend};
+
+/// How full nodes finalize blocks.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FinalityMode {
+	/// Finalize blocks once they have `confirmationDepth` confirmations.
+	Confirmations,
+	/// Finalize blocks by GRANDPA voting. The voters are managed on chain.
+	Grandpa,
+}
+
+/// Confirmations used by chains whose spec doesn't set `confirmationDepth`.
+pub const DEFAULT_CONFIRMATION_DEPTH: u32 = 20;
+
+/// Finalize blocks as soon as they are `depth` blocks below a new best block.
+///
+/// Runs until the client stops sending import notifications.
+pub fn confirmation_finality<Block, B, C>(client: Arc<C>, depth: u32) -> impl Future<Output = ()> where
+	Block: BlockT,
+	B: Backend<Block, Blake2Hasher>,
+	C: BlockchainEvents<Block> + HeaderBackend<Block> + Finalizer<Block, Blake2Hasher, B>,
+{
+	client.import_notification_stream()
+		.filter(|notification| future::ready(notification.is_new_best))
+		.for_each(move |notification| {
+			if let Err(e) = finalize_confirmed(&*client, *notification.header.number(), depth) {
+				warn!("Failed to finalize confirmed blocks: {}", e);
+			}
+			future::ready(())
+		})
+}
+
+/// Finalize the best chain's block `depth` blocks below `best`, if it isn't already.
+fn finalize_confirmed<Block, B, C>(client: &C, best: NumberFor<Block>, depth: u32) -> Result<(), String> where
+	Block: BlockT,
+	B: Backend<Block, Blake2Hasher>,
+	C: HeaderBackend<Block> + Finalizer<Block, Blake2Hasher, B>,
+{
+	if best < depth.into() {
+		return Ok(())
+	}
+
+	let target = best.saturating_sub(depth.into());
+	if target <= client.info().finalized_number {
+		return Ok(())
+	}
+
+	let hash = client.hash(target)
+		.map_err(|e| format!("Looking up block {} failed: {:?}", target, e))?
+		.ok_or_else(|| format!("Block {} is not on the best chain", target))?;
+
+	debug!("Finalizing block #{} ({}) with {} confirmations", target, hash, depth);
+	client.finalize_block(BlockId::Hash(hash), None, true)
+		.map_err(|e| format!("Finalizing block {} failed: {:?}", hash, e))
+}
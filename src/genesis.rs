@@ -0,0 +1,176 @@
+//! The `genesis` subcommand, which builds a chain spec from a config file.
+//!
+//! An example config, in TOML:
+//!
+//! ```toml
+//! name = "CLE Coin Testnet"
+//! id = "cle_coin_testnet"
+//! protocol_id = "cle-test"
+//! boot_nodes = ["/dns4/example.com/tcp/30333/p2p/QmPQwJBLMZTsJxFC4AFEGAiXSN9R49aJ8B2UNE6FaLdhFd"]
+//! sudo = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"
+//! council = ["5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"]
+//! difficulty = 5000
+//! reward = 1000000000000
+//! halving_interval = 5256000
+//! max_supply = 2100000000000000000
+//! token_symbol = "CLE"
+//! token_decimals = 12
+//!
+//! [[endowments]]
+//! account = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"
+//! amount = 1000000000000000
+//!
+//! [properties]
+//! confirmationDepth = 20
+//! ```
+//!
+//! The same fields can be given as JSON.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use structopt::StructOpt;
+use sp_core::crypto::Ss58Codec;
+use grandpa_primitives::AuthorityId as GrandpaId;
+use runtime::{AccountId, Balance, BlockNumber};
+use sc_cli::error;
+use crate::chain_spec::{self, ChainSpec};
+use crate::pow::{Algorithm, SS58_PREFIX};
+
+/// The `genesis` subcommand.
+#[derive(Debug, StructOpt, Clone)]
+pub struct GenesisCmd {
+	/// Genesis config file. Files ending in `.json` are read as JSON, all others as TOML.
+	#[structopt(parse(from_os_str))]
+	config: PathBuf,
+
+	/// Where to write the raw chain spec. Defaults to standard output.
+	#[structopt(long, short, parse(from_os_str))]
+	output: Option<PathBuf>,
+}
+
+/// An account and its balance at genesis.
+#[derive(Debug, Clone, Deserialize)]
+struct Endowment {
+	/// SS58 address of the account.
+	account: String,
+	amount: Balance,
+}
+
+/// The contents of a genesis config file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GenesisFile {
+	/// Human readable name of the chain.
+	name: String,
+	/// Chain id, which also names the node's database directory.
+	id: String,
+	#[serde(default)]
+	protocol_id: Option<String>,
+	#[serde(default)]
+	boot_nodes: Vec<String>,
+	/// SS58 address of the sudo key.
+	sudo: String,
+	#[serde(default)]
+	endowments: Vec<Endowment>,
+	/// SS58 addresses of the initial GRANDPA voters. Only used with GRANDPA finality.
+	#[serde(default)]
+	grandpa_voters: Vec<String>,
+	/// SS58 addresses of the initial council members.
+	#[serde(default)]
+	council: Vec<String>,
+	difficulty: u64,
+	/// The reward of the first blocks.
+	reward: Balance,
+	/// Blocks between halvings of the reward. Zero never halves it.
+	#[serde(default)]
+	halving_interval: BlockNumber,
+	/// Issuance stops once the total issuance reaches this. Zero doesn't cap it.
+	#[serde(default)]
+	max_supply: Balance,
+	#[serde(default)]
+	algorithm: Algorithm,
+	#[serde(default)]
+	token_symbol: Option<String>,
+	#[serde(default)]
+	token_decimals: Option<u8>,
+	/// Additional chain spec properties, such as `finality` or `checkpoints`.
+	#[serde(default)]
+	properties: Map<String, Value>,
+}
+
+impl GenesisCmd {
+	pub fn run(self) -> error::Result<()> {
+		let file = read_genesis_file(&self.config)?;
+		let spec = build_spec(file)?;
+		let json = spec.to_json(true)?;
+
+		match self.output {
+			Some(path) => fs::write(path, json)?,
+			None => println!("{}", json),
+		}
+
+		Ok(())
+	}
+}
+
+fn read_genesis_file(path: &Path) -> Result<GenesisFile, String> {
+	let contents = fs::read_to_string(path)
+		.map_err(|e| format!("Reading {} failed: {}", path.display(), e))?;
+
+	match path.extension().and_then(|ext| ext.to_str()) {
+		Some("json") => serde_json::from_str(&contents)
+			.map_err(|e| format!("Invalid genesis config {}: {}", path.display(), e)),
+		_ => toml::from_str(&contents)
+			.map_err(|e| format!("Invalid genesis config {}: {}", path.display(), e)),
+	}
+}
+
+fn build_spec(file: GenesisFile) -> Result<ChainSpec, String> {
+	let sudo = chain_spec::parse_account(&file.sudo)?;
+	let endowments = file.endowments.iter()
+		.map(|e| Ok((chain_spec::parse_account(&e.account)?, e.amount)))
+		.collect::<Result<Vec<(AccountId, Balance)>, String>>()?;
+	let voters = file.grandpa_voters.iter()
+		.map(|v| GrandpaId::from_ss58check(v).map_err(|e| format!("Invalid GRANDPA voter {}: {:?}", v, e)))
+		.collect::<Result<Vec<_>, String>>()?;
+	let council = file.council.iter()
+		.map(|m| chain_spec::parse_account(m))
+		.collect::<Result<Vec<_>, String>>()?;
+	let (difficulty, reward, algorithm) = (file.difficulty, file.reward, file.algorithm);
+	let (halving_interval, max_supply) = (file.halving_interval, file.max_supply);
+
+	let mut properties = file.properties;
+	properties.insert("ss58Format".into(), json!(SS58_PREFIX));
+	properties.insert("powAlgorithm".into(), json!(algorithm));
+	// New chains have no history from before seals named their author
+	properties.entry("authorRequiredFrom").or_insert_with(|| json!(0));
+	if let Some(symbol) = file.token_symbol {
+		properties.insert("tokenSymbol".into(), json!(symbol));
+	}
+	if let Some(decimals) = file.token_decimals {
+		properties.insert("tokenDecimals".into(), json!(decimals));
+	}
+
+	Ok(ChainSpec::from_genesis(
+		&file.name,
+		&file.id,
+		move || chain_spec::genesis_config(
+			sudo.clone(),
+			endowments.clone(),
+			voters.clone(),
+			council.clone(),
+			difficulty.into(),
+			reward,
+			halving_interval,
+			max_supply,
+			algorithm,
+		),
+		file.boot_nodes,
+		None,
+		file.protocol_id.as_ref().map(|id| id.as_str()),
+		Some(properties),
+		None,
+	))
+}
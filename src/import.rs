@@ -0,0 +1,383 @@
+//! Block import checks that CLE Coin enforces on top of `sc_consensus_pow`.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use codec::{Encode, Decode};
+use serde::Deserialize;
+use sp_core::H256;
+use sp_api::ApiExt;
+use sp_runtime::generic::BlockId;
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT, ProvideRuntimeApi, Saturating};
+use sp_blockchain::{HeaderBackend, HeaderMetadata, lowest_common_ancestor};
+use sp_consensus::{
+	BlockImport, BlockImportParams, BlockCheckParams, ImportResult, Error as ConsensusError,
+};
+use sp_consensus_pow::POW_ENGINE_ID;
+use sc_client_api::backend::well_known_cache_keys;
+use runtime::{Call, TimestampCall, UncheckedExtrinsic, MedianTimeApi};
+use crate::pow::{PreDigest, Seal};
+
+/// How far ahead of the local clock a block's timestamp may be.
+const MAX_FUTURE_DRIFT: Duration = Duration::from_secs(15);
+
+/// A block that the chain must contain.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+pub struct Checkpoint {
+	pub number: u32,
+	pub hash: H256,
+}
+
+/// Settings for the checks `CheckedBlockImport` makes.
+#[derive(Clone, Default, Debug)]
+pub struct ImportConfig {
+	/// Blocks that the chain must contain, usually from the chain spec.
+	pub checkpoints: Vec<Checkpoint>,
+	/// How far below the best block a new block may fork off. Unlimited if `None`.
+	pub max_reorg_depth: Option<u32>,
+	/// The first block whose seal must name its author. Blocks below it may
+	/// carry seals from before authors existed. Never required if `None`.
+	pub author_required_from: Option<u32>,
+}
+
+/// Wraps a block import and rejects blocks that break CLE Coin's consensus rules
+/// before handing them on:
+///
+/// * The seal's author and vote must be the ones in the PoW pre-runtime digest,
+///   which the runtime rewards and tallies. Since both are part of the hashed
+///   work, this binds the reward and the vote to whoever did the work. Seals
+///   below `author_required_from` may name neither, as the blocks mined before
+///   seals named an author don't, whether or not the block has a digest.
+/// * A block at the height of a checkpoint must be that checkpoint. Forks
+///   from below a checkpoint therefore can't be imported past it.
+/// * A block must not fork off below the last finalized block or, if a
+///   maximum reorg depth is set, more than that many blocks below the best block.
+/// * A block's timestamp must be at most `MAX_FUTURE_DRIFT` ahead of the local
+///   clock, and must not be earlier than the median timestamp of the blocks
+///   before it, as reported by the runtime. Blocks imported without a body,
+///   as on light clients, skip these checks.
+pub struct CheckedBlockImport<I, C> {
+	inner: I,
+	client: Arc<C>,
+	config: ImportConfig,
+}
+
+impl<I, C> CheckedBlockImport<I, C> {
+	pub fn new(inner: I, client: Arc<C>, config: ImportConfig) -> Self {
+		Self { inner, client, config }
+	}
+}
+
+impl<I: Clone, C> Clone for CheckedBlockImport<I, C> {
+	fn clone(&self) -> Self {
+		Self {
+			inner: self.inner.clone(),
+			client: self.client.clone(),
+			config: self.config.clone(),
+		}
+	}
+}
+
+/// The block's PoW pre-runtime digest, naming its author and their vote.
+fn pre_digest<B: BlockT>(header: &B::Header) -> Option<PreDigest> {
+	header.digest().logs().iter()
+		.filter_map(|item| item.as_pre_runtime())
+		.find(|(id, _)| *id == POW_ENGINE_ID)
+		.and_then(|(_, mut data)| PreDigest::decode(&mut data).ok())
+}
+
+/// The PoW seal, which the verifier has moved to the post digests.
+fn seal<B: BlockT, T>(block: &BlockImportParams<B, T>) -> Option<Seal> {
+	block.post_digests.iter()
+		.filter_map(|item| item.as_seal())
+		.find(|(id, _)| *id == POW_ENGINE_ID)
+		.and_then(|(_, mut data)| Seal::decode(&mut data).ok())
+}
+
+/// The timestamp set by the block's timestamp inherent.
+pub(crate) fn timestamp<B: BlockT>(body: &[B::Extrinsic]) -> Option<u64> {
+	body.iter()
+		.filter_map(|xt| UncheckedExtrinsic::decode(&mut &xt.encode()[..]).ok())
+		.find_map(|xt| match xt.function {
+			Call::Timestamp(TimestampCall::set(now)) => Some(now),
+			_ => None,
+		})
+}
+
+/// Check that the seal and the pre-runtime digest name the same author and vote,
+/// or that the seal names none where that is still allowed.
+fn check_author(seal: &Seal, digest: Option<&PreDigest>, required: bool) -> Result<(), ConsensusError> {
+	let (author, digest) = match (&seal.author, digest) {
+		(Some(author), Some(digest)) => (author, digest),
+		(None, _) if !required => return Ok(()),
+		(None, None) => return Err(ConsensusError::ClientImport(
+			"Block has no author, which blocks at its height must name".into(),
+		)),
+		(Some(author), None) => return Err(ConsensusError::ClientImport(format!(
+			"Seal author {} has no matching PoW author digest", author,
+		))),
+		(None, Some(digest)) => return Err(ConsensusError::ClientImport(format!(
+			"Digest author {} is missing from the seal", digest.author,
+		))),
+	};
+
+	if *author != digest.author {
+		return Err(ConsensusError::ClientImport(format!(
+			"Seal author {} does not match digest author {}", author, digest.author,
+		)))
+	}
+	if seal.vote != digest.vote {
+		return Err(ConsensusError::ClientImport(format!(
+			"Seal vote {:?} does not match digest vote {:?}", seal.vote, digest.vote,
+		)))
+	}
+
+	Ok(())
+}
+
+impl<I, C> CheckedBlockImport<I, C> {
+	fn check_checkpoints<B>(&self, header: &B::Header) -> Result<(), ConsensusError> where
+		B: BlockT<Hash = H256>,
+	{
+		check_checkpoint(&self.config.checkpoints, *header.number(), header.hash())
+	}
+
+	fn check_reorg_depth<B>(&self, parent_hash: B::Hash) -> Result<(), ConsensusError> where
+		B: BlockT,
+		C: HeaderBackend<B> + HeaderMetadata<B, Error = sp_blockchain::Error>,
+	{
+		let info = self.client.info();
+		let fork_point = lowest_common_ancestor(&*self.client, parent_hash, info.best_hash)
+			.map_err(|e| ConsensusError::ClientImport(e.to_string()))?;
+
+		check_fork_point(fork_point.number, info.finalized_number, info.best_number, self.config.max_reorg_depth)
+	}
+
+	fn check_timestamp<B, T>(&self, block: &BlockImportParams<B, T>) -> Result<(), ConsensusError> where
+		B: BlockT,
+		C: ProvideRuntimeApi,
+		C::Api: MedianTimeApi<B>,
+	{
+		let body = match &block.body {
+			Some(body) => body,
+			None => return Ok(()),
+		};
+
+		let now = SystemTime::now().duration_since(UNIX_EPOCH)
+			.map_err(|e| ConsensusError::ClientImport(format!("Local clock is before the epoch: {:?}", e)))?;
+
+		let parent = BlockId::Hash(*block.header.parent_hash());
+		let api = self.client.runtime_api();
+		let runtime_error = |e| ConsensusError::ClientImport(format!("Runtime call failed: {:?}", e));
+
+		// Runtimes from before the rule have no median to check against
+		let median = if api.has_api::<dyn MedianTimeApi<B>>(&parent).map_err(runtime_error)? {
+			Some(api.median_time_past(&parent).map_err(runtime_error)?)
+		} else {
+			None
+		};
+
+		check_timestamp_bounds(timestamp::<B>(body), now, median)
+	}
+}
+
+/// Check that block `hash` at height `number` is the checkpoint at that height, if there is one.
+fn check_checkpoint<N>(checkpoints: &[Checkpoint], number: N, hash: H256) -> Result<(), ConsensusError> where
+	N: PartialEq + From<u32>,
+{
+	let checkpoint = checkpoints.iter()
+		.find(|checkpoint| number == checkpoint.number.into());
+
+	match checkpoint {
+		Some(checkpoint) if checkpoint.hash != hash => Err(ConsensusError::ClientImport(format!(
+			"Block {} conflicts with checkpoint {} at #{}", hash, checkpoint.hash, checkpoint.number,
+		))),
+		_ => Ok(()),
+	}
+}
+
+/// Check that a block forking off at `fork_point` neither reverts the
+/// finalized block nor forks off more than `max_reorg_depth` below the best block.
+fn check_fork_point<N>(fork_point: N, finalized: N, best: N, max_reorg_depth: Option<u32>) -> Result<(), ConsensusError> where
+	N: PartialOrd + Saturating + From<u32> + Display + Copy,
+{
+	if fork_point < finalized {
+		return Err(ConsensusError::ClientImport(format!(
+			"Block forks off at #{}, below the finalized block #{}", fork_point, finalized,
+		)))
+	}
+
+	if let Some(max_reorg_depth) = max_reorg_depth {
+		let depth = best.saturating_sub(fork_point);
+		if depth > N::from(max_reorg_depth) {
+			return Err(ConsensusError::ClientImport(format!(
+				"Block forks off {} blocks below the best block, more than the maximum of {}",
+				depth, max_reorg_depth,
+			)))
+		}
+	}
+
+	Ok(())
+}
+
+/// Check that a block has a timestamp, at most `MAX_FUTURE_DRIFT` after `now`
+/// and, if there is one, not before the `median` of the previous blocks.
+fn check_timestamp_bounds(timestamp: Option<u64>, now: Duration, median: Option<u64>) -> Result<(), ConsensusError> {
+	let timestamp = timestamp
+		.ok_or_else(|| ConsensusError::ClientImport("Block has no timestamp".into()))?;
+
+	let latest = (now + MAX_FUTURE_DRIFT).as_millis() as u64;
+	if timestamp > latest {
+		return Err(ConsensusError::ClientImport(format!(
+			"Block timestamp {} is more than {:?} in the future", timestamp, MAX_FUTURE_DRIFT,
+		)))
+	}
+
+	match median {
+		Some(median) if timestamp < median => Err(ConsensusError::ClientImport(format!(
+			"Block timestamp {} is before the median {} of the previous blocks", timestamp, median,
+		))),
+		_ => Ok(()),
+	}
+}
+
+impl<B, I, C> BlockImport<B> for CheckedBlockImport<I, C> where
+	B: BlockT<Hash = H256>,
+	I: BlockImport<B, Error = ConsensusError>,
+	C: HeaderBackend<B> + HeaderMetadata<B, Error = sp_blockchain::Error> + ProvideRuntimeApi,
+	C::Api: MedianTimeApi<B>,
+{
+	type Error = ConsensusError;
+	type Transaction = I::Transaction;
+
+	fn check_block(
+		&mut self,
+		block: BlockCheckParams<B>,
+	) -> Result<ImportResult, Self::Error> {
+		self.inner.check_block(block)
+	}
+
+	fn import_block(
+		&mut self,
+		block: BlockImportParams<B, Self::Transaction>,
+		new_cache: HashMap<well_known_cache_keys::Id, Vec<u8>>,
+	) -> Result<ImportResult, Self::Error> {
+		let seal = seal(&block)
+			.ok_or_else(|| ConsensusError::ClientImport("Block has no PoW seal".into()))?;
+		let author_required = self.config.author_required_from
+			.map_or(false, |from| *block.header.number() >= from.into());
+		check_author(&seal, pre_digest::<B>(&block.header).as_ref(), author_required)?;
+		self.check_checkpoints::<B>(&block.post_header())?;
+
+		// Blocks with an unknown parent are left for the inner import to reject
+		let parent_hash = *block.header.parent_hash();
+		let parent = self.client.header(BlockId::Hash(parent_hash))
+			.map_err(|e| ConsensusError::ClientImport(e.to_string()))?;
+		if parent.is_some() {
+			self.check_reorg_depth::<B>(parent_hash)?;
+			self.check_timestamp(&block)?;
+		}
+
+		self.inner.import_block(block, new_cache)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::U256;
+	use crate::pow::{AccountId, Vote};
+
+	fn seal(author: Option<AccountId>, vote: Option<Vote>) -> Seal {
+		Seal {
+			difficulty: U256::from(1),
+			work: H256::repeat_byte(1),
+			nonce: H256::repeat_byte(2),
+			author,
+			vote,
+		}
+	}
+
+	fn digest(author: AccountId, vote: Option<Vote>) -> PreDigest {
+		PreDigest { author, vote }
+	}
+
+	#[test]
+	fn author_and_vote_must_match_digest() {
+		let alice = AccountId::new([1; 32]);
+		let bob = AccountId::new([2; 32]);
+		let up = Some(Vote::IncreaseReward);
+
+		assert!(check_author(&seal(Some(alice.clone()), up), Some(&digest(alice.clone(), up)), true).is_ok());
+		assert!(check_author(&seal(Some(alice.clone()), up), Some(&digest(bob.clone(), up)), true).is_err());
+		assert!(check_author(&seal(Some(alice.clone()), up), Some(&digest(alice.clone(), None)), true).is_err());
+		assert!(check_author(&seal(Some(alice.clone()), None), Some(&digest(alice.clone(), up)), false).is_err());
+	}
+
+	#[test]
+	fn legacy_blocks_need_no_author_until_required() {
+		let alice = AccountId::new([1; 32]);
+
+		assert!(check_author(&seal(None, None), None, false).is_ok());
+		assert!(check_author(&seal(None, None), None, true).is_err());
+		assert!(check_author(&seal(Some(alice.clone()), None), None, false).is_err());
+		assert!(check_author(&seal(None, None), Some(&digest(alice.clone(), None)), false).is_ok());
+		assert!(check_author(&seal(None, None), Some(&digest(alice, None)), true).is_err());
+	}
+
+	#[test]
+	fn timestamps_must_not_be_too_far_in_the_future() {
+		let now = Duration::from_secs(1000);
+
+		assert!(check_timestamp_bounds(Some(1_000_000), now, None).is_ok());
+		assert!(check_timestamp_bounds(Some(1_015_000), now, None).is_ok());
+		assert!(check_timestamp_bounds(Some(1_015_001), now, None).is_err());
+	}
+
+	#[test]
+	fn timestamps_must_not_precede_the_median() {
+		let now = Duration::from_secs(1000);
+
+		assert!(check_timestamp_bounds(Some(900_000), now, Some(900_000)).is_ok());
+		assert!(check_timestamp_bounds(Some(899_999), now, Some(900_000)).is_err());
+		// Runtimes without the median only check the local clock
+		assert!(check_timestamp_bounds(Some(0), now, None).is_ok());
+	}
+
+	#[test]
+	fn blocks_must_have_a_timestamp() {
+		assert!(check_timestamp_bounds(None, Duration::from_secs(1000), None).is_err());
+	}
+
+	#[test]
+	fn blocks_at_a_checkpoint_must_be_the_checkpoint() {
+		let checkpoints = vec![
+			Checkpoint { number: 10, hash: H256::repeat_byte(1) },
+			Checkpoint { number: 20, hash: H256::repeat_byte(2) },
+		];
+
+		assert!(check_checkpoint(&checkpoints, 10u32, H256::repeat_byte(1)).is_ok());
+		assert!(check_checkpoint(&checkpoints, 20u32, H256::repeat_byte(2)).is_ok());
+		assert!(check_checkpoint(&checkpoints, 10u32, H256::repeat_byte(2)).is_err());
+		assert!(check_checkpoint(&checkpoints, 20u32, H256::repeat_byte(3)).is_err());
+		// Other heights are unconstrained
+		assert!(check_checkpoint(&checkpoints, 15u32, H256::repeat_byte(3)).is_ok());
+		assert!(check_checkpoint(&[], 10u32, H256::repeat_byte(3)).is_ok());
+	}
+
+	#[test]
+	fn blocks_must_not_fork_off_below_the_finalized_block() {
+		assert!(check_fork_point(5u32, 5, 10, None).is_ok());
+		assert!(check_fork_point(4u32, 5, 10, None).is_err());
+	}
+
+	#[test]
+	fn blocks_must_not_fork_off_deeper_than_the_maximum() {
+		assert!(check_fork_point(90u32, 0, 100, Some(10)).is_ok());
+		assert!(check_fork_point(89u32, 0, 100, Some(10)).is_err());
+		assert!(check_fork_point(0u32, 0, 100, None).is_ok());
+		// Extending the best block, or a fork above it, is no reorg at all
+		assert!(check_fork_point(100u32, 0, 100, Some(0)).is_ok());
+	}
+}
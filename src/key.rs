@@ -0,0 +1,169 @@
+//! The `key` subcommands, for creating accounts and managing the node's keys.
+
+use std::path::PathBuf;
+use bip39::{Mnemonic, MnemonicType, Language};
+use structopt::StructOpt;
+use sp_core::{Pair, ed25519, sr25519, crypto::Ss58Codec, hexdisplay::HexDisplay};
+use sc_cli::{VersionInfo, error};
+use sc_keystore::Store as Keystore;
+use app_dirs::{AppInfo, AppDataType};
+use crate::chain_spec::{self, ChainSpec};
+use crate::cli::load_spec;
+use crate::pow::MINING_KEY_TYPE;
+
+/// The signature schemes keys can use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+	Sr25519,
+	Ed25519,
+}
+
+impl std::str::FromStr for Scheme {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, String> {
+		match s {
+			"sr25519" => Ok(Scheme::Sr25519),
+			"ed25519" => Ok(Scheme::Ed25519),
+			_ => Err(format!("Unknown scheme {}. Use sr25519 or ed25519.", s)),
+		}
+	}
+}
+
+/// The `key` subcommands.
+#[derive(Debug, StructOpt, Clone)]
+pub enum KeyCmd {
+	/// Generate a new key and print its secret phrase and address.
+	#[structopt(name = "generate")]
+	Generate {
+		/// Signature scheme of the key.
+		#[structopt(long, default_value = "sr25519")]
+		scheme: Scheme,
+
+		/// Number of words in the secret phrase: 12, 15, 18, 21 or 24.
+		#[structopt(long, default_value = "12")]
+		words: usize,
+	},
+
+	/// Print the public key and address of a secret phrase, seed or URI such as `//Alice`.
+	#[structopt(name = "inspect")]
+	Inspect {
+		/// The secret to inspect.
+		suri: String,
+
+		/// Signature scheme of the key.
+		#[structopt(long, default_value = "sr25519")]
+		scheme: Scheme,
+	},
+
+	/// Insert a key into the node's keystore.
+	///
+	/// `mine` keys (sr25519) name the account that a mining node pays block
+	/// rewards to when it is started without `--author`. `gran` keys (ed25519)
+	/// vote in GRANDPA.
+	#[structopt(name = "insert")]
+	Insert {
+		/// The secret phrase, seed or URI of the key.
+		suri: String,
+
+		/// Type of the key: `mine` or `gran`.
+		#[structopt(long, default_value = "mine")]
+		key_type: String,
+
+		/// Chain whose keystore to insert into. Takes the same values as the node's `--chain`.
+		#[structopt(long, default_value = "")]
+		chain: String,
+
+		/// Base path of the node's data, as passed to the node's `--base-path`.
+		#[structopt(long, short = "d", parse(from_os_str))]
+		base_path: Option<PathBuf>,
+
+		/// Keystore directory. Overrides the one derived from `--chain` and `--base-path`.
+		#[structopt(long, parse(from_os_str))]
+		keystore_path: Option<PathBuf>,
+	},
+}
+
+impl KeyCmd {
+	pub fn run(self, version: &VersionInfo) -> error::Result<()> {
+		match self {
+			KeyCmd::Generate { scheme, words } => {
+				let words = MnemonicType::for_word_count(words)
+					.map_err(|e| format!("Invalid number of words: {}", e))?;
+				let mnemonic = Mnemonic::new(words, Language::English);
+				print_key(mnemonic.phrase(), scheme)
+			}
+			KeyCmd::Inspect { suri, scheme } => print_key(&suri, scheme),
+			KeyCmd::Insert { suri, key_type, chain, base_path, keystore_path } => {
+				let keystore_path = match keystore_path {
+					Some(path) => path,
+					None => default_keystore_path(version, &chain, base_path)?,
+				};
+				insert_key(&suri, &key_type, keystore_path)
+			}
+		}
+	}
+}
+
+fn print_key(suri: &str, scheme: Scheme) -> error::Result<()> {
+	match scheme {
+		Scheme::Sr25519 => print_pair::<sr25519::Pair>(suri),
+		Scheme::Ed25519 => print_pair::<ed25519::Pair>(suri),
+	}
+}
+
+fn print_pair<P: Pair>(suri: &str) -> error::Result<()> where
+	P::Public: Ss58Codec + AsRef<[u8]>,
+{
+	let public = P::from_string(suri, None)
+		.map_err(|e| format!("Invalid secret {:?}", e))?
+		.public();
+
+	println!("Secret: {}", suri);
+	println!("Public key (hex): 0x{}", HexDisplay::from(&public.as_ref()));
+	println!("Address (SS58): {}", public.to_ss58check_with_version(chain_spec::ss58_format()));
+
+	Ok(())
+}
+
+/// The keystore of `chain` under the node's base path, `<base>/chains/<id>/keystore`.
+fn default_keystore_path(version: &VersionInfo, chain: &str, base_path: Option<PathBuf>) -> Result<PathBuf, String> {
+	let spec = match load_spec(chain)? {
+		Some(spec) => spec,
+		None => ChainSpec::from_json_file(PathBuf::from(chain))?,
+	};
+
+	let base_path = match base_path {
+		Some(path) => path,
+		None => app_dirs::get_app_root(
+			AppDataType::UserData,
+			&AppInfo { name: version.executable_name, author: version.author },
+		).map_err(|e| format!("Can't find the default base path: {}", e))?,
+	};
+
+	Ok(base_path.join("chains").join(spec.id()).join("keystore"))
+}
+
+fn insert_key(suri: &str, key_type: &str, keystore_path: PathBuf) -> error::Result<()> {
+	let (key_type, public) = match key_type {
+		"mine" => (MINING_KEY_TYPE, public_bytes::<sr25519::Pair>(suri)?),
+		"gran" => (grandpa_primitives::KEY_TYPE, public_bytes::<ed25519::Pair>(suri)?),
+		_ => return Err(format!("Unknown key type {}. Use mine or gran.", key_type).into()),
+	};
+
+	let keystore = Keystore::open(keystore_path.clone(), None)
+		.map_err(|e| format!("Opening keystore {} failed: {:?}", keystore_path.display(), e))?;
+	keystore.write().insert_unknown(key_type, suri, &public)
+		.map_err(|e| format!("Inserting key failed: {:?}", e))?;
+
+	println!("Inserted key 0x{} into {}", HexDisplay::from(&public), keystore_path.display());
+	Ok(())
+}
+
+fn public_bytes<P: Pair>(suri: &str) -> Result<Vec<u8>, String> where
+	P::Public: AsRef<[u8]>,
+{
+	P::from_string(suri, None)
+		.map(|pair| pair.public().as_ref().to_vec())
+		.map_err(|e| format!("Invalid secret {:?}", e))
+}
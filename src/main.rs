@@ -7,7 +7,14 @@ mod chain_spec;
 #[macro_use]
 mod service;
 mod cli;
+mod finality;
+mod genesis;
+mod import;
+mod key;
 mod pow;
+mod rpc;
+mod select_chain;
+mod tx;
 
 pub use sc_cli::{VersionInfo, IntoExit, error};
 
@@ -22,5 +29,8 @@ fn main() -> Result<(), cli::error::Error> {
 		support_url: "support.anonymous.an",
 	};
 
+	// Print every address with CLE Coin's prefix
+	sp_core::crypto::set_default_ss58_version(chain_spec::ss58_format());
+
 	cli::run(std::env::args(), cli::Exit, version)
 }
@@ -1,74 +1,206 @@
-use std::sync::Arc;
-use sp_core::{U256, H256};
+use std::sync::{Arc, mpsc::{self, RecvTimeoutError}, atomic::{AtomicBool, AtomicU64, Ordering}};
+use std::cmp::min;
+use std::thread;
+use std::time::{Duration, Instant};
+use parking_lot::Mutex;
+use serde::Serialize;
+use sp_core::{U256, H256, crypto::KeyTypeId};
 use sp_runtime::generic::BlockId;
 use sp_runtime::traits::{
-	Block as BlockT, ProvideRuntimeApi, UniqueSaturatedInto,
+	Block as BlockT, One, ProvideRuntimeApi, UniqueSaturatedInto,
 };
 use sp_blockchain::HeaderBackend;
 use sc_client_api::backend::AuxStore;
 use codec::{Encode, Decode};
 use sc_consensus_pow::{PowAlgorithm, Error};
 use sp_consensus_pow::Seal as RawSeal;
-use sha3::{Sha3_256, Digest};
 use rand::{thread_rng, SeedableRng, rngs::SmallRng};
-use std::time::Duration;
 use sp_consensus_pow::DifficultyApi;
+use sp_api::ApiExt;
+use runtime::AlgorithmApi;
 
-/// Specific PoW Algorithm that uses Sha3 hashing.
-/// Needs a reference to the client so it can grab the difficulty from the runtime.
-pub struct Sha3Algorithm<C> {
-	client: Arc<C>,
+pub use cle_coin_pow::{
+	AccountId, Algorithm, Compute, PreDigest, Seal, Vote, Work, SS58_PREFIX, hash_meets_difficulty, verify_seal,
+};
+
+/// Key type of the sr25519 key whose account receives the rewards of blocks
+/// mined by a node started without `--author`.
+pub const MINING_KEY_TYPE: KeyTypeId = KeyTypeId(*b"mine");
+
+/// How often the mining threads check whether they have been cancelled, in nonces.
+const CANCEL_CHECK_INTERVAL: u32 = 1024;
+
+/// How often `mine` checks whether a new best block or a remote solution has arrived.
+const BEST_BLOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long work is offered to remote miners when the node doesn't mine locally.
+/// Afterwards a fresh block is built so that new transactions get included.
+const REMOTE_MINING_ROUND: Duration = Duration::from_secs(10);
+
+/// Counters describing the local miner's progress. Shared between the mining
+/// threads, the informant and the RPC.
+#[derive(Default)]
+pub struct MiningStats {
+	hashes: AtomicU64,
+	blocks_found: AtomicU64,
+	hashrate: AtomicU64,
+	difficulty: Mutex<U256>,
 }
 
-impl<C> Sha3Algorithm<C> {
-	pub fn new(client: Arc<C>) -> Self {
-		Self { client }
+/// A point in time copy of `MiningStats`.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MiningStatsSnapshot {
+	/// Total number of hashes computed since the node started.
+	pub hashes: u64,
+	/// Number of seals found since the node started.
+	pub blocks_found: u64,
+	/// Hashes per second during the most recent mining round.
+	pub hashrate: u64,
+	/// Difficulty of the most recent mining round.
+	pub difficulty: U256,
+}
+
+impl MiningStats {
+	pub fn snapshot(&self) -> MiningStatsSnapshot {
+		MiningStatsSnapshot {
+			hashes: self.hashes.load(Ordering::Relaxed),
+			blocks_found: self.blocks_found.load(Ordering::Relaxed),
+			hashrate: self.hashrate.load(Ordering::Relaxed),
+			difficulty: *self.difficulty.lock(),
+		}
 	}
 }
 
-/// Determine whether the given hash satisfies the given difficulty.
-/// The test is done by multiplying the two together. If the product
-/// overflows the bounds of U256, then the product (and thus the hash)
-/// was too high.
-fn hash_meets_difficulty(hash: &H256, difficulty: U256) -> bool {
-	let num_hash = U256::from(&hash[..]);
-	let (_, overflowed) = num_hash.overflowing_mul(difficulty);
+/// Hands work out to miners outside the node and collects their solutions.
+#[derive(Default)]
+pub struct RemoteMining {
+	work: Mutex<Option<Work>>,
+	solutions: Mutex<Vec<Seal>>,
+}
+
+impl RemoteMining {
+	/// The work currently being mined, if the node is mining at all.
+	pub fn work(&self) -> Option<Work> {
+		self.work.lock().clone()
+	}
+
+	/// Submit a seal for the current work. Returns whether the seal solves it.
+	pub fn submit(&self, pre_hash: H256, seal: Seal) -> bool {
+		let work = self.work.lock();
+		let solves = match &*work {
+			Some(work) => {
+				let expected = work.compute(seal.nonce);
+				work.pre_hash == pre_hash
+					&& seal.author == expected.author
+					&& seal.vote == expected.vote
+					&& verify_seal(&seal, &pre_hash, work.difficulty, work.algorithm)
+			}
+			None => false,
+		};
+
+		if solves {
+			self.solutions.lock().push(seal);
+		}
+		solves
+	}
+
+	fn begin(&self, work: Work) {
+		*self.work.lock() = Some(work);
+		self.solutions.lock().clear();
+	}
 
-	!overflowed
+	fn end(&self) {
+		*self.work.lock() = None;
+		self.solutions.lock().clear();
+	}
+
+	fn take_solution(&self) -> Option<Seal> {
+		self.solutions.lock().pop()
+	}
 }
 
-/// A Seal struct that will be encoded to a Vec<u8> as used as the
-/// RawSeal type
-#[derive(Clone, PartialEq, Eq, Encode, Decode, Debug)]
-pub struct Seal {
-	pub difficulty: U256,
-	pub work: H256,
-	pub nonce: H256,
+/// PoW Algorithm that hashes with one of the `Algorithm`s, as chosen by the runtime.
+/// Needs a reference to the client so it can grab the difficulty and algorithm from the runtime.
+pub struct HashAlgorithm<C> {
+	client: Arc<C>,
+	/// The algorithm for blocks whose runtime doesn't provide `AlgorithmApi`.
+	default_algorithm: Algorithm,
+	author: Option<AccountId>,
+	vote: Option<Vote>,
+	/// The first block whose seal names the author and vote, if any.
+	author_required_from: Option<u32>,
+	threads: usize,
+	stats: Arc<MiningStats>,
+	remote: Arc<RemoteMining>,
 }
 
-/// TODO Docs
-#[derive(Clone, PartialEq, Eq, Encode, Decode, Debug)]
-pub struct Compute {
-	pub difficulty: U256,
-	pub pre_hash: H256,
-	pub nonce: H256,
+impl<C> HashAlgorithm<C> {
+	/// Create an instance without an author. Suitable for block import, which
+	/// never mines.
+	pub fn new(client: Arc<C>, default_algorithm: Algorithm) -> Self {
+		Self {
+			client,
+			default_algorithm,
+			author: None,
+			vote: None,
+			author_required_from: None,
+			threads: 0,
+			stats: Default::default(),
+			remote: Default::default(),
+		}
+	}
+
+	/// Create an instance that mines seals for `author`, voting for `vote`, on
+	/// the given number of threads, recording its progress in `stats`. Seals
+	/// name the author and vote from block `author_required_from` on, and have
+	/// the original format before it or if it is `None`. Work is also offered to
+	/// remote miners through `remote`. With zero threads, mining relies on
+	/// remote miners alone.
+	pub fn miner(
+		client: Arc<C>,
+		default_algorithm: Algorithm,
+		author: AccountId,
+		vote: Option<Vote>,
+		author_required_from: Option<u32>,
+		threads: usize,
+		stats: Arc<MiningStats>,
+		remote: Arc<RemoteMining>,
+	) -> Self {
+		Self {
+			client,
+			default_algorithm,
+			author: Some(author),
+			vote,
+			author_required_from,
+			threads,
+			stats,
+			remote,
+		}
+	}
 }
 
-impl Compute {
-	pub fn compute(self) -> Seal {
-		let work = H256::from_slice(Sha3_256::digest(&self.encode()[..]).as_slice());
+impl<C: ProvideRuntimeApi> HashAlgorithm<C> {
+	/// The algorithm that seals of the block built on `parent` must use.
+	fn algorithm<B: BlockT<Hash=H256>>(&self, parent: &BlockId<B>) -> Result<Algorithm, Error<B>> where
+		C::Api: AlgorithmApi<B>,
+	{
+		let api = self.client.runtime_api();
+		let has_api = api.has_api::<dyn AlgorithmApi<B>>(parent)
+			.map_err(|e| Error::Environment(format!("Checking runtime for AlgorithmApi failed: {:?}", e)))?;
 
-		Seal {
-			nonce: self.nonce,
-			difficulty: self.difficulty,
-			work: H256::from(work), //TODO Can I just use `work` here directly?
+		if !has_api {
+			return Ok(self.default_algorithm)
 		}
+
+		api.algorithm(parent)
+			.map_err(|e| Error::Environment(format!("Fetching algorithm from runtime failed: {:?}", e)))
 	}
 }
 
-impl<B: BlockT<Hash=H256>, C> PowAlgorithm<B> for Sha3Algorithm<C> where
+impl<B: BlockT<Hash=H256>, C> PowAlgorithm<B> for HashAlgorithm<C> where
 	C: HeaderBackend<B> + AuxStore + ProvideRuntimeApi,
-	C::Api: DifficultyApi<B, U256>,
+	C::Api: DifficultyApi<B, U256> + AlgorithmApi<B>,
 {
 	type Difficulty = U256;
 
@@ -81,7 +213,7 @@ impl<B: BlockT<Hash=H256>, C> PowAlgorithm<B> for Sha3Algorithm<C> where
 
 	fn verify(
 		&self,
-		_parent: &BlockId<B>,
+		parent: &BlockId<B>,
 		pre_hash: &H256,
 		seal: &RawSeal,
 		difficulty: Self::Difficulty
@@ -92,59 +224,136 @@ impl<B: BlockT<Hash=H256>, C> PowAlgorithm<B> for Sha3Algorithm<C> where
 			Err(_) => return Ok(false),
 		};
 
-		// See whether the hash meets the difficulty requirement. If not, fail fast.
-		if !hash_meets_difficulty(&seal.work, difficulty) {
-			return Ok(false)
-		}
-
-		// Make sure the provided work actually comes from the correct pre_hash
-		let compute = Compute {
-			difficulty,
-			pre_hash: *pre_hash,
-			nonce: seal.nonce,
-		};
-
-		if compute.compute() != seal {
-			return Ok(false)
-		}
-
-		Ok(true)
+		Ok(verify_seal(&seal, pre_hash, difficulty, self.algorithm(parent)?))
 	}
 
 	fn mine(
 		&self,
-		_parent: &BlockId<B>,
+		parent: &BlockId<B>,
 		pre_hash: &H256,
 		difficulty: Self::Difficulty,
-		round: u32 // The number of nonces to try suring this call
+		round: u32 // The number of nonces each thread tries during this call
 	) -> Result<Option<RawSeal>, Error<B>> {
+		let author = self.author.clone()
+			.ok_or_else(|| Error::Environment("Mining requires an author".into()))?;
+		let parent_hash = self.client.expect_block_hash_from_id(parent)
+			.map_err(|e| Error::Environment(format!("Fetching parent hash failed: {:?}", e)))?;
+		let parent_number = self.client.expect_block_number_from_id(parent)
+			.map_err(|e| Error::Environment(format!("Fetching parent number failed: {:?}", e)))?;
+		// Nodes that predate author-bound seals reject them, so they are only
+		// mined once the chain requires them.
+		let bind_author = self.author_required_from
+			.map_or(false, |from| parent_number + One::one() >= from.into());
+
 		// Get a randomness source from the environment and fail if one isn't available
 		let mut rng = SmallRng::from_rng(&mut thread_rng())
 			.map_err(|e| Error::Environment(format!("Initialize RNG failed for mining: {:?}", e)))?;
 
-		// Loop the specified number of times
-		for _ in 0..round {
-			// Artificially throttle the mining
-			std::thread::sleep(Duration::new(0, 1_000_000));
-
-			// Choose a new nonce
-			let nonce = H256::random_using(&mut rng);
-
-			// Calculate the seal
-			let compute = Compute {
-				difficulty,
-				pre_hash: *pre_hash,
-				nonce,
-			};
-			let seal = compute.compute();
-
-			// If we solved the PoW then return, otherwise loop again
-			if hash_meets_difficulty(&seal.work, difficulty) {
-				return Ok(Some(seal.encode()))
+		// Each thread searches its own range of `round` nonces, starting from a random point
+		let start = U256::from(&H256::random_using(&mut rng)[..]);
+
+		*self.stats.difficulty.lock() = difficulty;
+		let algorithm = self.algorithm(parent)?;
+		let work = Work {
+			pre_hash: *pre_hash,
+			difficulty,
+			parent_hash,
+			author: author.clone(),
+			algorithm,
+			vote: self.vote,
+			bind_author,
+		};
+		self.remote.begin(work.clone());
+		let started = Instant::now();
+		let hashes_before = self.stats.hashes.load(Ordering::Relaxed);
+
+		let cancel = Arc::new(AtomicBool::new(false));
+		let (sender, receiver) = mpsc::channel();
+
+		let mut workers = Vec::with_capacity(self.threads);
+		for index in 0..self.threads {
+			let first = start.overflowing_add(U256::from(index) * U256::from(round)).0;
+			let work = work.clone();
+			let cancel = cancel.clone();
+			let sender = sender.clone();
+			let stats = self.stats.clone();
+
+			let worker = thread::Builder::new()
+				.name(format!("pow-miner-{}", index))
+				.spawn(move || {
+					if let Some(seal) = mine_round(&work, first, round, &cancel, &stats) {
+						let _ = sender.send(seal);
+					}
+				})
+				.map_err(|e| Error::Environment(format!("Spawning mining thread failed: {:?}", e)))?;
+			workers.push(worker);
+		}
+		drop(sender);
+
+		// Wait for a local or remote solution, stopping early if the chain moves on without us
+		let result = loop {
+			if let Some(seal) = self.remote.take_solution() {
+				break Some(seal.encode())
+			}
+
+			match receiver.recv_timeout(BEST_BLOCK_POLL_INTERVAL) {
+				Ok(seal) => break Some(seal.encode()),
+				// Every thread exhausted its range without a solution
+				Err(RecvTimeoutError::Disconnected) if self.threads > 0 => break None,
+				// There are no local threads, so only remote miners are working
+				Err(RecvTimeoutError::Disconnected) => {
+					if started.elapsed() >= REMOTE_MINING_ROUND {
+						break None
+					}
+					thread::sleep(BEST_BLOCK_POLL_INTERVAL);
+				}
+				Err(RecvTimeoutError::Timeout) => (),
+			}
+
+			if self.client.info().best_hash != parent_hash {
+				break None
 			}
+		};
+
+		self.remote.end();
+		cancel.store(true, Ordering::Relaxed);
+		for worker in workers {
+			let _ = worker.join();
+		}
+
+		let hashes = self.stats.hashes.load(Ordering::Relaxed) - hashes_before;
+		let elapsed = started.elapsed().as_millis().max(1) as u64;
+		self.stats.hashrate.store(hashes.saturating_mul(1000) / elapsed, Ordering::Relaxed);
+		if result.is_some() {
+			self.stats.blocks_found.fetch_add(1, Ordering::Relaxed);
+		}
+
+		Ok(result)
+	}
+}
+
+/// Try `round` consecutive nonces beginning at `first`. Returns early, without
+/// a solution, once `cancel` is set. The number of hashes computed is added to `stats`.
+fn mine_round(
+	work: &Work,
+	first: U256,
+	round: u32,
+	cancel: &AtomicBool,
+	stats: &MiningStats,
+) -> Option<Seal> {
+	let mut nonce = first;
+	let mut remaining = round;
+
+	while remaining > 0 && !cancel.load(Ordering::Relaxed) {
+		let (seal, tried) = work.mine_range(nonce, min(remaining, CANCEL_CHECK_INTERVAL));
+		stats.hashes.fetch_add(tried.into(), Ordering::Relaxed);
+		if seal.is_some() {
+			return seal
 		}
 
-		// Tried the specified number of rounds and never found a solution
-		Ok(None)
+		nonce = nonce.overflowing_add(tried.into()).0;
+		remaining -= tried;
 	}
+
+	None
 }
@@ -0,0 +1,316 @@
+//! CLE Coin specific RPCs.
+
+use std::sync::Arc;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use jsonrpc_core::{Error, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use codec::Decode;
+use sp_core::{Bytes, H256, U256};
+use sp_runtime::generic::BlockId;
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sc_client_api::BlockBody;
+use sp_consensus_pow::{DifficultyApi, TimestampApi, POW_ENGINE_ID};
+use runtime::{Balance, BlockNumber, EmissionApi, PowParamsApi, TreasuryApi as TreasuryRuntimeApi, DAYS};
+use crate::pow::{MiningStats, MiningStatsSnapshot, RemoteMining, Seal, Work};
+
+/// The RPC extensions a CLE Coin node exposes on top of the default Substrate ones.
+pub type RpcExtension = jsonrpc_core::IoHandler<sc_rpc::Metadata>;
+
+/// Error code returned when the client or runtime fails to answer a query.
+const RUNTIME_ERROR: i64 = 1;
+
+fn runtime_error(message: &str, e: impl Debug) -> Error {
+	Error {
+		code: ErrorCode::ServerError(RUNTIME_ERROR),
+		message: message.into(),
+		data: Some(format!("{:?}", e).into()),
+	}
+}
+
+/// Error code returned when a submitted seal can't be decoded.
+const INVALID_SEAL: i64 = 2;
+
+/// Error code returned when the chain has too few mined blocks for an estimate.
+const TOO_FEW_BLOCKS: i64 = 3;
+
+/// The most blocks `pow_networkHashrate` reads: a day's worth.
+const MAX_HASHRATE_WINDOW: u32 = DAYS;
+
+/// Information about this node's miner, and the remote mining protocol.
+#[rpc]
+pub trait MiningApi {
+	/// Hashrate, blocks found and difficulty of this node's miner.
+	#[rpc(name = "mining_stats")]
+	fn stats(&self) -> Result<MiningStatsSnapshot>;
+
+	/// The work this node is currently mining. `None` when the node isn't mining.
+	#[rpc(name = "mining_getWork")]
+	fn get_work(&self) -> Result<Option<Work>>;
+
+	/// Submit a SCALE encoded seal solving the work for `pre_hash`.
+	/// Returns whether the seal was accepted.
+	#[rpc(name = "mining_submitWork")]
+	fn submit_work(&self, pre_hash: H256, seal: Bytes) -> Result<bool>;
+}
+
+/// Implements `MiningApi` on top of the miner's shared state.
+pub struct Mining {
+	stats: Arc<MiningStats>,
+	remote: Arc<RemoteMining>,
+}
+
+impl Mining {
+	pub fn new(stats: Arc<MiningStats>, remote: Arc<RemoteMining>) -> Self {
+		Self { stats, remote }
+	}
+}
+
+impl MiningApi for Mining {
+	fn stats(&self) -> Result<MiningStatsSnapshot> {
+		Ok(self.stats.snapshot())
+	}
+
+	fn get_work(&self) -> Result<Option<Work>> {
+		Ok(self.remote.work())
+	}
+
+	fn submit_work(&self, pre_hash: H256, seal: Bytes) -> Result<bool> {
+		let seal = Seal::decode(&mut &seal[..]).map_err(|e| Error {
+			code: ErrorCode::ServerError(INVALID_SEAL),
+			message: "Unable to decode seal".into(),
+			data: Some(format!("{:?}", e).into()),
+		})?;
+
+		Ok(self.remote.submit(pre_hash, seal))
+	}
+}
+
+/// Proof of Work state of the chain.
+#[rpc]
+pub trait PowApi<BlockHash> {
+	/// The difficulty of the block built on top of `at`, or on top of the best block.
+	#[rpc(name = "pow_difficulty")]
+	fn difficulty(&self, at: Option<BlockHash>) -> Result<U256>;
+
+	/// The reward paid to the author of the block built on top of `at`, or on
+	/// top of the best block, after the treasury's share.
+	#[rpc(name = "pow_reward")]
+	fn reward(&self, at: Option<BlockHash>) -> Result<Balance>;
+
+	/// The reward the emission schedule pays for block `number`, before the supply cap.
+	#[rpc(name = "pow_rewardAt")]
+	fn reward_at(&self, number: BlockNumber, at: Option<BlockHash>) -> Result<Balance>;
+
+	/// The total issuance after block `number`, if every block until then pays its scheduled reward.
+	#[rpc(name = "pow_projectedIssuance")]
+	fn projected_issuance(&self, number: BlockNumber, at: Option<BlockHash>) -> Result<Balance>;
+
+	/// The decoded PoW seal of the given block. `None` for blocks without a seal, such as genesis.
+	#[rpc(name = "pow_decodeSeal")]
+	fn decode_seal(&self, block_hash: BlockHash) -> Result<Option<Seal>>;
+
+	/// Estimated network hashrate, in hashes per second, over the last `window`
+	/// blocks. `window` must be between 1 and a day's worth of blocks.
+	#[rpc(name = "pow_networkHashrate")]
+	fn network_hashrate(&self, window: u32) -> Result<U256>;
+}
+
+/// Implements `PowApi` by querying the client.
+pub struct Pow<C, Block> {
+	client: Arc<C>,
+	_marker: PhantomData<Block>,
+}
+
+impl<C, Block> Pow<C, Block> {
+	pub fn new(client: Arc<C>) -> Self {
+		Self { client, _marker: PhantomData }
+	}
+}
+
+impl<C, Block> Pow<C, Block> where
+	Block: BlockT<Hash = H256>,
+	C: HeaderBackend<Block>,
+{
+	fn at_or_best(&self, at: Option<H256>) -> BlockId<Block> {
+		BlockId::Hash(at.unwrap_or_else(|| self.client.info().best_hash))
+	}
+
+	fn header(&self, hash: H256) -> Result<Block::Header> {
+		self.client.header(BlockId::Hash(hash))
+			.map_err(|e| runtime_error("Unable to fetch header", e))?
+			.ok_or_else(|| runtime_error("Unknown block", hash))
+	}
+
+	/// The timestamp of block `hash`. Full nodes read it from the block's body,
+	/// which they keep after pruning the block's state. Light clients, which
+	/// don't store bodies, ask the runtime instead.
+	fn timestamp(&self, hash: H256) -> Result<u64> where
+		C: BlockBody<Block> + ProvideRuntimeApi,
+		C::Api: TimestampApi<Block, u64>,
+	{
+		let body = self.client.block_body(&BlockId::Hash(hash)).ok().and_then(|body| body);
+		match body.and_then(|body| crate::import::timestamp::<Block>(&body)) {
+			Some(timestamp) => Ok(timestamp),
+			None => self.client.runtime_api().timestamp(&BlockId::Hash(hash))
+				.map_err(|e| runtime_error("Unable to query timestamp", e)),
+		}
+	}
+
+	fn seal(&self, header: &Block::Header) -> Result<Option<Seal>> {
+		let raw = header.digest().logs().iter()
+			.filter_map(|item| item.as_seal())
+			.find(|(id, _)| *id == POW_ENGINE_ID)
+			.map(|(_, raw)| raw);
+
+		raw.map(|mut raw| Seal::decode(&mut raw).map_err(|e| runtime_error("Unable to decode seal", e)))
+			.transpose()
+	}
+}
+
+impl<C, Block> PowApi<H256> for Pow<C, Block> where
+	Block: BlockT<Hash = H256>,
+	C: HeaderBackend<Block> + BlockBody<Block> + ProvideRuntimeApi + Send + Sync + 'static,
+	C::Api: DifficultyApi<Block, U256> + TimestampApi<Block, u64> + PowParamsApi<Block, Balance>
+		+ EmissionApi<Block, BlockNumber, Balance>,
+{
+	fn difficulty(&self, at: Option<H256>) -> Result<U256> {
+		self.client.runtime_api().difficulty(&self.at_or_best(at))
+			.map_err(|e| runtime_error("Unable to query difficulty", e))
+	}
+
+	fn reward(&self, at: Option<H256>) -> Result<Balance> {
+		self.client.runtime_api().reward(&self.at_or_best(at))
+			.map_err(|e| runtime_error("Unable to query reward", e))
+	}
+
+	fn reward_at(&self, number: BlockNumber, at: Option<H256>) -> Result<Balance> {
+		self.client.runtime_api().reward_at(&self.at_or_best(at), number)
+			.map_err(|e| runtime_error("Unable to query reward", e))
+	}
+
+	fn projected_issuance(&self, number: BlockNumber, at: Option<H256>) -> Result<Balance> {
+		self.client.runtime_api().projected_issuance(&self.at_or_best(at), number)
+			.map_err(|e| runtime_error("Unable to query projected issuance", e))
+	}
+
+	fn decode_seal(&self, block_hash: H256) -> Result<Option<Seal>> {
+		self.seal(&self.header(block_hash)?)
+	}
+
+	fn network_hashrate(&self, window: u32) -> Result<U256> {
+		if window == 0 || window > MAX_HASHRATE_WINDOW {
+			return Err(Error::invalid_params(format!(
+				"Window must be between 1 and {} blocks", MAX_HASHRATE_WINDOW,
+			)))
+		}
+
+		let best = self.client.info().best_hash;
+		let mut header = self.header(best)?;
+		let mut seal = self.seal(&header)?;
+		let mut work = U256::zero();
+		let mut counted = 0;
+
+		// A seal with difficulty `d` takes `d` hashes on average to find. Each
+		// block is timed from its parent, so only blocks whose parent was mined
+		// too are counted. Genesis has no seal, and no meaningful timestamp.
+		while counted < window {
+			let difficulty = match &seal {
+				Some(seal) => seal.difficulty,
+				None => break,
+			};
+			let parent = self.header(*header.parent_hash())?;
+			let parent_seal = self.seal(&parent)?;
+			if parent_seal.is_none() {
+				break
+			}
+
+			work = work.saturating_add(difficulty);
+			counted += 1;
+			header = parent;
+			seal = parent_seal;
+		}
+
+		if counted == 0 {
+			return Err(Error {
+				code: ErrorCode::ServerError(TOO_FEW_BLOCKS),
+				message: "Too few mined blocks to estimate the hashrate".into(),
+				data: None,
+			})
+		}
+
+		let newest = self.timestamp(best)?;
+		let oldest = self.timestamp(header.hash())?;
+
+		let elapsed_millis = newest.saturating_sub(oldest);
+		if elapsed_millis == 0 {
+			return Ok(U256::zero())
+		}
+
+		Ok(work.saturating_mul(1000.into()) / U256::from(elapsed_millis))
+	}
+}
+
+/// The funds held by the treasury.
+#[rpc]
+pub trait TreasuryApi<BlockHash> {
+	/// The treasury's balance at `at`, or at the best block.
+	#[rpc(name = "treasury_balance")]
+	fn balance(&self, at: Option<BlockHash>) -> Result<Balance>;
+}
+
+/// Implements `TreasuryApi` by querying the runtime.
+pub struct Treasury<C, Block> {
+	client: Arc<C>,
+	_marker: PhantomData<Block>,
+}
+
+impl<C, Block> Treasury<C, Block> {
+	pub fn new(client: Arc<C>) -> Self {
+		Self { client, _marker: PhantomData }
+	}
+}
+
+impl<C, Block> TreasuryApi<H256> for Treasury<C, Block> where
+	Block: BlockT<Hash = H256>,
+	C: HeaderBackend<Block> + ProvideRuntimeApi + Send + Sync + 'static,
+	C::Api: TreasuryRuntimeApi<Block, Balance>,
+{
+	fn balance(&self, at: Option<H256>) -> Result<Balance> {
+		let at = BlockId::Hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		self.client.runtime_api().treasury_balance(&at)
+			.map_err(|e| runtime_error("Unable to query treasury balance", e))
+	}
+}
+
+/// Build the RPC extensions of a full node.
+pub fn create_full<C, Block>(
+	client: Arc<C>,
+	stats: Arc<MiningStats>,
+	remote: Arc<RemoteMining>,
+) -> RpcExtension where
+	Block: BlockT<Hash = H256>,
+	C: HeaderBackend<Block> + BlockBody<Block> + ProvideRuntimeApi + Send + Sync + 'static,
+	C::Api: DifficultyApi<Block, U256> + TimestampApi<Block, u64> + PowParamsApi<Block, Balance>
+		+ EmissionApi<Block, BlockNumber, Balance> + TreasuryRuntimeApi<Block, Balance>,
+{
+	let mut io = jsonrpc_core::IoHandler::default();
+	io.extend_with(MiningApi::to_delegate(Mining::new(stats, remote)));
+	io.extend_with(PowApi::to_delegate(Pow::new(client.clone())));
+	io.extend_with(TreasuryApi::to_delegate(Treasury::new(client)));
+	io
+}
+
+/// Build the RPC extensions of a light client.
+pub fn create_light<C, Block>(client: Arc<C>) -> RpcExtension where
+	Block: BlockT<Hash = H256>,
+	C: HeaderBackend<Block> + BlockBody<Block> + ProvideRuntimeApi + Send + Sync + 'static,
+	C::Api: DifficultyApi<Block, U256> + TimestampApi<Block, u64> + PowParamsApi<Block, Balance>
+		+ EmissionApi<Block, BlockNumber, Balance> + TreasuryRuntimeApi<Block, Balance>,
+{
+	let mut io = jsonrpc_core::IoHandler::default();
+	io.extend_with(PowApi::to_delegate(Pow::new(client.clone())));
+	io.extend_with(TreasuryApi::to_delegate(Treasury::new(client)));
+	io
+}
@@ -0,0 +1,246 @@
+//! Fork choice by accumulated proof of work.
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+use sp_core::{U256, Blake2Hasher};
+use sp_runtime::generic::BlockId;
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT, NumberFor};
+use sp_blockchain::{Backend as BlockchainBackend, HeaderBackend};
+use sp_consensus::{SelectChain, Error as ConsensusError};
+use sc_client_api::backend::Backend;
+use sc_consensus_pow::PowAux;
+
+/// Selects the leaf with the greatest total difficulty as the best chain, so
+/// that a fork with many easy blocks can't outweigh one with more real work.
+///
+/// Total difficulties are the ones `sc_consensus_pow` records in the aux store
+/// when importing blocks. Among equally heavy leaves, the one the backend lists
+/// first wins.
+pub struct HeaviestChain<B, Block> {
+	backend: Arc<B>,
+	_phantom: PhantomData<Block>,
+}
+
+impl<B, Block> Clone for HeaviestChain<B, Block> {
+	fn clone(&self) -> Self {
+		Self {
+			backend: self.backend.clone(),
+			_phantom: PhantomData,
+		}
+	}
+}
+
+impl<B, Block> HeaviestChain<B, Block> where
+	B: Backend<Block, Blake2Hasher>,
+	Block: BlockT,
+{
+	pub fn new(backend: Arc<B>) -> Self {
+		Self { backend, _phantom: PhantomData }
+	}
+
+	fn header(&self, hash: Block::Hash) -> Result<Block::Header, ConsensusError> {
+		self.backend.blockchain().header(BlockId::Hash(hash))
+			.map_err(|e| ConsensusError::ChainLookup(e.to_string()))?
+			.ok_or_else(|| ConsensusError::ChainLookup(format!("Missing header for block {}", hash)))
+	}
+
+	fn total_difficulty(&self, hash: &Block::Hash) -> Result<U256, ConsensusError> {
+		PowAux::<U256>::read::<_, Block>(&*self.backend, hash)
+			.map(|aux| aux.total_difficulty)
+			.map_err(|e| ConsensusError::ChainLookup(e.to_string()))
+	}
+
+	/// All leaves, heaviest first.
+	fn leaves_by_work(&self) -> Result<Vec<Block::Hash>, ConsensusError> {
+		by_work(self.leaves()?, |leaf| self.total_difficulty(leaf))
+	}
+
+	/// The number and parent of a block.
+	fn number_and_parent(&self, hash: &Block::Hash) -> Result<(NumberFor<Block>, Block::Hash), ConsensusError> {
+		let header = self.header(*hash)?;
+		Ok((*header.number(), *header.parent_hash()))
+	}
+}
+
+/// `leaves` sorted heaviest first. Ties keep their order.
+fn by_work<H, E>(leaves: Vec<H>, total_difficulty: impl Fn(&H) -> Result<U256, E>) -> Result<Vec<H>, E> {
+	let mut leaves = leaves
+		.into_iter()
+		.map(|leaf| Ok((total_difficulty(&leaf)?, leaf)))
+		.collect::<Result<Vec<_>, E>>()?;
+
+	// Stable, so ties keep the backend's order
+	leaves.sort_by(|a, b| b.0.cmp(&a.0));
+
+	Ok(leaves.into_iter().map(|(_, leaf)| leaf).collect())
+}
+
+/// The first of `leaves` whose chain contains the target, walked back to
+/// `maybe_max_number` if given. `number_and_parent` looks up blocks.
+fn first_containing<H, N, E>(
+	target_hash: H,
+	target_number: N,
+	leaves: Vec<H>,
+	maybe_max_number: Option<N>,
+	number_and_parent: impl Fn(&H) -> Result<(N, H), E>,
+) -> Result<Option<H>, E> where
+	H: Copy + PartialEq,
+	N: Copy + PartialOrd,
+{
+	for leaf in leaves {
+		let mut hash = leaf;
+		let (mut number, mut parent) = number_and_parent(&hash)?;
+		while maybe_max_number.map_or(false, |max| number > max) {
+			hash = parent;
+			let (n, p) = number_and_parent(&hash)?;
+			number = n;
+			parent = p;
+		}
+		let candidate = hash;
+
+		while number > target_number {
+			hash = parent;
+			let (n, p) = number_and_parent(&hash)?;
+			number = n;
+			parent = p;
+		}
+		if hash == target_hash {
+			return Ok(Some(candidate))
+		}
+	}
+
+	Ok(None)
+}
+
+impl<B, Block> SelectChain<Block> for HeaviestChain<B, Block> where
+	B: Backend<Block, Blake2Hasher>,
+	Block: BlockT,
+{
+	fn leaves(&self) -> Result<Vec<Block::Hash>, ConsensusError> {
+		self.backend.blockchain().leaves()
+			.map_err(|e| ConsensusError::ChainLookup(e.to_string()))
+	}
+
+	fn best_chain(&self) -> Result<Block::Header, ConsensusError> {
+		let best = self.leaves_by_work()?
+			.into_iter()
+			.next()
+			.ok_or_else(|| ConsensusError::ChainLookup("No leaves in the blockchain".into()))?;
+
+		self.header(best)
+	}
+
+	/// The head of the heaviest fork that contains `target_hash`, walked back to
+	/// `maybe_max_number` if given.
+	fn finality_target(
+		&self,
+		target_hash: Block::Hash,
+		maybe_max_number: Option<NumberFor<Block>>,
+	) -> Result<Option<Block::Hash>, ConsensusError> {
+		let target_number = *self.header(target_hash)?.number();
+
+		first_containing(
+			target_hash,
+			target_number,
+			self.leaves_by_work()?,
+			maybe_max_number,
+			|hash| self.number_and_parent(hash),
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::HashMap;
+
+	/// Blocks by hash: their number, parent and total difficulty.
+	struct Chain(HashMap<u32, (u32, u32, U256)>);
+
+	impl Chain {
+		/// A chain with just the genesis block, `0`.
+		fn new() -> Self {
+			let mut blocks = HashMap::new();
+			blocks.insert(0, (0, 0, U256::zero()));
+			Chain(blocks)
+		}
+
+		/// Add `count` blocks of `difficulty` on top of `parent`, with hashes
+		/// counting up from `first`. Returns the last block's hash.
+		fn extend(&mut self, parent: u32, first: u32, count: u32, difficulty: u64) -> u32 {
+			let mut parent = parent;
+			for hash in first..first + count {
+				let (number, _, total) = self.0[&parent];
+				self.0.insert(hash, (number + 1, parent, total + difficulty));
+				parent = hash;
+			}
+			parent
+		}
+
+		fn total_difficulty(&self, hash: &u32) -> Result<U256, ()> {
+			Ok(self.0[hash].2)
+		}
+
+		fn number_and_parent(&self, hash: &u32) -> Result<(u32, u32), ()> {
+			let (number, parent, _) = self.0[hash];
+			Ok((number, parent))
+		}
+
+		fn number(&self, hash: u32) -> u32 {
+			self.0[&hash].0
+		}
+	}
+
+	#[test]
+	fn heavy_short_fork_beats_long_light_fork() {
+		let mut chain = Chain::new();
+		let long = chain.extend(0, 100, 10, 10);
+		let heavy = chain.extend(0, 200, 3, 100);
+
+		let leaves = by_work(vec![long, heavy], |leaf| chain.total_difficulty(leaf)).unwrap();
+		assert_eq!(leaves, vec![heavy, long]);
+	}
+
+	#[test]
+	fn ties_keep_the_backend_order() {
+		let mut chain = Chain::new();
+		let a = chain.extend(0, 100, 2, 50);
+		let b = chain.extend(0, 200, 4, 25);
+
+		assert_eq!(by_work(vec![a, b], |leaf| chain.total_difficulty(leaf)).unwrap(), vec![a, b]);
+		assert_eq!(by_work(vec![b, a], |leaf| chain.total_difficulty(leaf)).unwrap(), vec![b, a]);
+	}
+
+	#[test]
+	fn finality_target_is_the_heaviest_fork_containing_the_target() {
+		let mut chain = Chain::new();
+		let trunk = chain.extend(0, 100, 5, 10);
+		let light = chain.extend(trunk, 200, 10, 10);
+		let heavy = chain.extend(103, 300, 3, 100);
+		let leaves = by_work(vec![light, heavy], |leaf| chain.total_difficulty(leaf)).unwrap();
+		let target = |hash| first_containing(hash, chain.number(hash), leaves.clone(), None, |h| chain.number_and_parent(h));
+
+		// Below the fork point both forks contain the target, and the heavier wins
+		assert_eq!(target(102).unwrap(), Some(heavy));
+		// Only the light fork contains the trunk's tip
+		assert_eq!(target(trunk).unwrap(), Some(light));
+		// Nothing contains a block that isn't in the chain at its height
+		chain.0.insert(999, (2, 100, U256::zero()));
+		let target = first_containing(999, 2, leaves.clone(), None, |h| chain.number_and_parent(h));
+		assert_eq!(target.unwrap(), None);
+	}
+
+	#[test]
+	fn finality_target_walks_back_to_the_max_number() {
+		let mut chain = Chain::new();
+		let tip = chain.extend(0, 100, 10, 10);
+		let number_and_parent = |h: &u32| chain.number_and_parent(h);
+
+		// Blocks 100 to 109 are numbers 1 to 10
+		assert_eq!(first_containing(101, 2, vec![tip], Some(6), number_and_parent).unwrap(), Some(105));
+		assert_eq!(first_containing(101, 2, vec![tip], Some(20), number_and_parent).unwrap(), Some(tip));
+		assert_eq!(first_containing(101, 2, vec![tip], Some(2), number_and_parent).unwrap(), Some(101));
+		// A maximum below the target leaves no block that contains it
+		assert_eq!(first_containing(105, 6, vec![tip], Some(3), number_and_parent).unwrap(), None);
+	}
+}
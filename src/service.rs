@@ -2,17 +2,24 @@
 
 use std::sync::Arc;
 use std::time::Duration;
-use sc_client::LongestChain;
-use runtime::{self, GenesisConfig, opaque::Block, RuntimeApi};
+use runtime::{self, AccountId, GenesisConfig, opaque::Block, RuntimeApi};
 use sc_service::{error::{Error as ServiceError}, AbstractService, Configuration, ServiceBuilder};
 use sp_inherents::InherentDataProviders;
 use sc_network::{config::DummyFinalityProofRequestBuilder, construct_simple_protocol};
 use sc_executor::native_executor_instance;
 pub use sc_executor::NativeExecutor;
-use sp_consensus_aura::sr25519::{AuthorityPair as AuraPair};
+use sp_consensus::import_queue::{BasicQueue, BoxBlockImport, BoxFinalityProofImport};
+use sc_consensus_pow::PowVerifier;
 use grandpa::{self, FinalityProofProvider as GrandpaFinalityProofProvider};
 use sc_basic_authority;
-use crate::pow::Sha3Algorithm;
+use crate::pow::{HashAlgorithm, MiningStats, PreDigest, RemoteMining, Vote, MINING_KEY_TYPE};
+use crate::rpc::{self, RpcExtension};
+use crate::import::{CheckedBlockImport, ImportConfig};
+use crate::select_chain::HeaviestChain;
+use crate::finality::FinalityMode;
+use codec::Encode;
+use sp_core::traits::BareCryptoStore;
+use sp_runtime::{MultiSigner, traits::IdentifyAccount};
 
 // Our native executor instance.
 native_executor_instance!(
@@ -43,15 +50,34 @@ pub fn cle_coin_inherent_data_providers() -> Result<InherentDataProviders, Servi
 ///
 /// Use this macro if you don't actually need the full service, but just the builder in order to
 /// be able to perform chain operations.
+///
+/// With GRANDPA finality, blocks are imported through GRANDPA's block import,
+/// and the link to the voter is returned alongside the builder. The block
+/// import is returned too, so that mined blocks go through the same checks.
+///
+/// Imported blocks must agree with the chain spec's checkpoints and, if a
+/// maximum reorg depth is given, must not fork off deeper than that.
 macro_rules! new_full_start {
 	($config:expr) => {{
+		new_full_start!($config, None)
+	}};
+	($config:expr, $max_reorg_depth:expr) => {{
+		let mut grandpa_link = None;
+		let mut mining_block_import: Option<sp_consensus::import_queue::BoxBlockImport<runtime::opaque::Block, _>> = None;
 		let inherent_data_providers = crate::service::cle_coin_inherent_data_providers()?;
+		let algorithm = crate::chain_spec::pow_algorithm(&$config.chain_spec)?;
+		let finality = crate::chain_spec::finality_mode(&$config.chain_spec)?;
+		let import_config = crate::import::ImportConfig {
+			checkpoints: crate::chain_spec::checkpoints(&$config.chain_spec)?,
+			max_reorg_depth: $max_reorg_depth,
+			author_required_from: crate::chain_spec::author_required_from(&$config.chain_spec)?,
+		};
 
 		let builder = sc_service::ServiceBuilder::new_full::<
 			runtime::opaque::Block, runtime::RuntimeApi, crate::service::Executor
 		>($config)?
 			.with_select_chain(|_config, backend| {
-				Ok(sc_client::LongestChain::new(backend.clone()))
+				Ok(crate::select_chain::HeaviestChain::new(backend.clone()))
 			})?
 			.with_transaction_pool(|config, client, _fetcher| {
 				let pool_api = sc_transaction_pool::FullChainApi::new(client.clone());
@@ -60,26 +86,82 @@ macro_rules! new_full_start {
 				let maintainable_pool = sp_transaction_pool::MaintainableTransactionPool::new(pool, maintainer);
 				Ok(maintainable_pool)
 			})?
-			.with_import_queue(|_config, client, select_chain, _transaction_pool| {
-				let import_queue = sc_consensus_pow::import_queue(
-					Box::new(client.clone()),
+			.with_import_queue(|_config, client, mut select_chain, _transaction_pool| {
+				let select_chain = select_chain.take()
+					.ok_or_else(|| sc_service::Error::SelectChainRequired)?;
+
+				let (block_import, justification_import): (sp_consensus::import_queue::BoxBlockImport<_, _>, _) =
+					match finality {
+						crate::finality::FinalityMode::Confirmations => {
+							let checked_block_import = crate::import::CheckedBlockImport::new(
+								client.clone(), client.clone(), import_config,
+							);
+							mining_block_import = Some(Box::new(checked_block_import.clone()) as _);
+
+							(Box::new(checked_block_import) as _, None)
+						}
+						crate::finality::FinalityMode::Grandpa => {
+							let (grandpa_block_import, link) = grandpa::block_import::<_, _, _, runtime::RuntimeApi, _>(
+								client.clone(), &*client, select_chain.clone(),
+							)?;
+							grandpa_link = Some(link);
+
+							let checked_block_import = crate::import::CheckedBlockImport::new(
+								grandpa_block_import.clone(), client.clone(), import_config,
+							);
+							mining_block_import = Some(Box::new(checked_block_import.clone()) as _);
+
+							(
+								Box::new(checked_block_import) as _,
+								Some(Box::new(grandpa_block_import) as _),
+							)
+						}
+					};
+
+				let verifier = sc_consensus_pow::PowVerifier::new(
 					client.clone(),
-					crate::pow::Sha3Algorithm::new(client.clone()),
+					crate::pow::HashAlgorithm::new(client.clone(), algorithm),
 					0,
-					select_chain,
+					Some(select_chain),
 					inherent_data_providers.clone(),
-				)?;
+				);
+				let import_queue = sp_consensus::import_queue::BasicQueue::new(
+					verifier,
+					block_import,
+					justification_import,
+					None,
+				);
 
 				Ok(import_queue)
 			})?;
 
-		(builder, inherent_data_providers)
+		(builder, mining_block_import, grandpa_link, inherent_data_providers)
 	}}
 }
 
 /// Builds a new service for a full client.
-pub fn new_full<C: Send + Default + 'static>(config: Configuration<C, GenesisConfig>)
-	-> Result<impl AbstractService, ServiceError>
+///
+/// When mining, `author` is the account that block rewards are paid to, or if
+/// it is `None`, the account of the first mining key in the keystore. It is
+/// placed in a PoW pre-runtime digest of every block this node mines, along
+/// with the node's `vote`, if any, and from the chain spec's
+/// `authorRequiredFrom` height on in the seal too. Mining runs on `threads` threads and records its progress in `stats`.
+/// The same work is offered to remote miners through the `mining_getWork` RPC.
+///
+/// Blocks that fork off more than `max_reorg_depth` blocks below the best block
+/// are rejected.
+///
+/// Blocks are finalized once they have the number of confirmations the chain
+/// spec asks for or, if the chain spec enables GRANDPA, by GRANDPA voting.
+/// Authorities with a GRANDPA key in their keystore vote, everyone else observes.
+pub fn new_full<C: Send + Default + 'static>(
+	config: Configuration<C, GenesisConfig>,
+	author: Option<AccountId>,
+	vote: Option<Vote>,
+	threads: usize,
+	stats: Arc<MiningStats>,
+	max_reorg_depth: Option<u32>,
+) -> Result<impl AbstractService, ServiceError>
 {
 	let is_authority = config.roles.is_authority();
 	let name = config.name.clone();
@@ -88,33 +170,108 @@ pub fn new_full<C: Send + Default + 'static>(config: Configuration<C, GenesisCon
 	// and should run the same protocols authorities do, but it should
 	// never actively participate in any consensus process.
 	let participates_in_consensus = is_authority && !config.sentry_mode;
+	let algorithm = crate::chain_spec::pow_algorithm(&config.chain_spec)?;
+	let finality = crate::chain_spec::finality_mode(&config.chain_spec)?;
+	let confirmation_depth = crate::chain_spec::confirmation_depth(&config.chain_spec)?;
+	let author_required_from = crate::chain_spec::author_required_from(&config.chain_spec)?;
 
-	let (builder, inherent_data_providers) = new_full_start!(config);
+	let (builder, block_import, grandpa_link, inherent_data_providers) =
+		new_full_start!(config, max_reorg_depth);
 
-	let service = builder.with_network_protocol(|_| Ok(NodeProtocol::new()))?
-		.with_finality_proof_provider(|_client, _backend|
-			Ok(Arc::new(()) as _)
-		)?
+	let remote = Arc::new(RemoteMining::default());
+
+	let (rpc_stats, rpc_remote) = (stats.clone(), remote.clone());
+	let service = builder
+		.with_rpc_extensions(|client, _pool, _backend, _fetcher, _remote_blockchain| -> Result<RpcExtension, _> {
+			Ok(rpc::create_full(client, rpc_stats, rpc_remote))
+		})?
+		.with_network_protocol(|_| Ok(NodeProtocol::new()))?
+		.with_finality_proof_provider(move |client, backend| Ok(match finality {
+			FinalityMode::Confirmations => Arc::new(()) as _,
+			FinalityMode::Grandpa => Arc::new(GrandpaFinalityProofProvider::new(backend, client)) as _,
+		}))?
 		.build()?;
 
+	match grandpa_link {
+		None => service.spawn_essential_task(
+			"confirmation-finality",
+			crate::finality::confirmation_finality(service.client(), confirmation_depth),
+		),
+		Some(grandpa_link) => {
+			// if the node isn't actively participating in consensus then it doesn't
+			// need a keystore.
+			let keystore = if participates_in_consensus {
+				Some(service.keystore())
+			} else {
+				None
+			};
+
+			let grandpa_config = grandpa::Config {
+				gossip_duration: Duration::from_millis(333),
+				justification_period: 512,
+				name: Some(name),
+				observer_enabled: true,
+				keystore,
+				is_authority,
+			};
+
+			if is_authority {
+				let voter_config = grandpa::GrandpaParams {
+					config: grandpa_config,
+					link: grandpa_link,
+					network: service.network(),
+					inherent_data_providers: inherent_data_providers.clone(),
+					on_exit: service.on_exit(),
+					telemetry_on_connect: Some(service.telemetry_on_connect_stream()),
+					voting_rule: grandpa::VotingRulesBuilder::default().build(),
+				};
+
+				// the GRANDPA voter task is considered infallible, i.e.
+				// if it fails we take down the service with it.
+				service.spawn_essential_task("grandpa-voter", grandpa::run_grandpa_voter(voter_config)?);
+			} else {
+				service.spawn_task("grandpa-observer", grandpa::run_grandpa_observer(
+					grandpa_config,
+					grandpa_link,
+					service.network(),
+					service.on_exit(),
+				)?);
+			}
+		}
+	}
+
 	if participates_in_consensus {
 		let proposer = sc_basic_authority::ProposerFactory {
 			client: service.client(),
 			transaction_pool: service.transaction_pool(),
 		};
 
-		// The number of rounds of mining to try in a single call
-		let rounds = 500;
+		// The number of nonces each mining thread tries in a single call
+		let rounds = 1_000_000;
+
+		let author = author
+			.or_else(|| service.keystore().read()
+				.sr25519_public_keys(MINING_KEY_TYPE)
+				.into_iter()
+				.next()
+				.map(|public| MultiSigner::from(public).into_account())
+			)
+			.ok_or_else(|| "Mining requires an author. Pass one with --author or insert a mining key with `cle-coin key insert`.")?;
 
 		let can_author_with =
 			sp_consensus::CanAuthorWithNativeVersion::new(service.client().executor().clone());
 
+		let block_import = block_import
+			.ok_or_else(|| "Mining requires the block import the import queue was built with.")?;
+
 		sc_consensus_pow::start_mine(
-			Box::new(service.client().clone()),
+			block_import,
 			service.client(),
-			Sha3Algorithm::new(service.client().clone()),
+			HashAlgorithm::miner(
+				service.client().clone(), algorithm, author.clone(), vote, author_required_from, threads, stats, remote,
+			),
 			proposer,
-			None,
+			Some(PreDigest { author, vote }.encode()),
 			rounds,
 			service.network(),
 			std::time::Duration::new(2, 0),
@@ -127,14 +284,25 @@ pub fn new_full<C: Send + Default + 'static>(config: Configuration<C, GenesisCon
 }
 
 /// Builds a new service for a light client.
+///
+/// Like full nodes, light clients finalize blocks once they have the number of
+/// confirmations the chain spec asks for, unless the chain spec enables GRANDPA.
 pub fn new_light<C: Send + Default + 'static>(config: Configuration<C, GenesisConfig>)
 	-> Result<impl AbstractService, ServiceError>
 {
 	let inherent_data_providers = cle_coin_inherent_data_providers()?;
+	let algorithm = crate::chain_spec::pow_algorithm(&config.chain_spec)?;
+	let finality = crate::chain_spec::finality_mode(&config.chain_spec)?;
+	let confirmation_depth = crate::chain_spec::confirmation_depth(&config.chain_spec)?;
+	let import_config = ImportConfig {
+		checkpoints: crate::chain_spec::checkpoints(&config.chain_spec)?,
+		max_reorg_depth: None,
+		author_required_from: crate::chain_spec::author_required_from(&config.chain_spec)?,
+	};
 
-	ServiceBuilder::new_light::<Block, RuntimeApi, Executor>(config)?
+	let service = ServiceBuilder::new_light::<Block, RuntimeApi, Executor>(config)?
 		.with_select_chain(|_config, backend| {
-			Ok(LongestChain::new(backend.clone()))
+			Ok(HeaviestChain::new(backend.clone()))
 		})?
 		.with_transaction_pool(|config, client, fetcher| {
 			let fetcher = fetcher
@@ -145,24 +313,60 @@ pub fn new_light<C: Send + Default + 'static>(config: Configuration<C, GenesisCo
 			let maintainable_pool = sp_transaction_pool::MaintainableTransactionPool::new(pool, maintainer);
 			Ok(maintainable_pool)
 		})?
-		.with_import_queue_and_fprb(|_config, client, _backend, _fetcher, select_chain, _tx_pool| {
-			let finality_proof_request_builder =
-				Box::new(DummyFinalityProofRequestBuilder::default()) as Box<_>;
+		.with_import_queue_and_fprb(|_config, client, backend, fetcher, select_chain, _tx_pool| {
+			let (block_import, finality_proof_import, finality_proof_request_builder):
+				(BoxBlockImport<_, _>, Option<BoxFinalityProofImport<_>>, _) = match finality
+			{
+				FinalityMode::Confirmations => (
+					Box::new(CheckedBlockImport::new(client.clone(), client.clone(), import_config)) as _,
+					None,
+					Box::new(DummyFinalityProofRequestBuilder::default()) as Box<_>,
+				),
+				FinalityMode::Grandpa => {
+					let fetch_checker = fetcher
+						.map(|fetcher| fetcher.checker().clone())
+						.ok_or_else(|| "Trying to start light import queue without active fetch checker")?;
+					let grandpa_block_import = grandpa::light_block_import::<_, _, _, RuntimeApi>(
+						client.clone(), backend, &*client.clone(), Arc::new(fetch_checker),
+					)?;
+					let finality_proof_request_builder =
+						grandpa_block_import.create_finality_proof_request_builder();
+
+					(
+						Box::new(CheckedBlockImport::new(grandpa_block_import.clone(), client.clone(), import_config)) as _,
+						Some(Box::new(grandpa_block_import) as _),
+						finality_proof_request_builder,
+					)
+				}
+			};
 
-			let import_queue = sc_consensus_pow::import_queue(
-				Box::new(client.clone()),
+			let verifier = PowVerifier::new(
 				client.clone(),
-				Sha3Algorithm::new(client.clone()),
+				HashAlgorithm::new(client.clone(), algorithm),
 				0,
 				select_chain,
 				inherent_data_providers.clone(),
-			)?;
+			);
+			let import_queue = BasicQueue::new(verifier, block_import, None, finality_proof_import);
 
 			Ok((import_queue, finality_proof_request_builder))
 		})?
+		.with_rpc_extensions(|client, _pool, _backend, _fetcher, _remote_blockchain| -> Result<RpcExtension, _> {
+			Ok(rpc::create_light(client))
+		})?
 		.with_network_protocol(|_| Ok(NodeProtocol::new()))?
-		.with_finality_proof_provider(|_client, _backend|
-			Ok(Arc::new(()) as _)
-		)?
-		.build()
+		.with_finality_proof_provider(move |client, backend| Ok(match finality {
+			FinalityMode::Confirmations => Arc::new(()) as _,
+			FinalityMode::Grandpa => Arc::new(GrandpaFinalityProofProvider::new(backend, client)) as _,
+		}))?
+		.build()?;
+
+	if finality == FinalityMode::Confirmations {
+		service.spawn_essential_task(
+			"confirmation-finality",
+			crate::finality::confirmation_finality(service.client(), confirmation_depth),
+		);
+	}
+
+	Ok(service)
 }
@@ -0,0 +1,194 @@
+//! The `tx` subcommands, for building and signing transactions offline.
+//!
+//! `tx build` runs wherever the account's nonce and the chain's genesis hash
+//! are known, and prints an unsigned transaction. `tx sign` needs nothing but
+//! that output and the secret key, so it can run on an air-gapped machine. It
+//! prints a signed extrinsic to submit with `author_submitExtrinsic`.
+
+use codec::{Encode, Decode};
+use structopt::StructOpt;
+use sp_core::{H256, Pair, sr25519, bytes::{from_hex, to_hex}};
+use sp_runtime::{MultiSigner, generic::Era, traits::IdentifyAccount};
+use sc_cli::error;
+use runtime::{
+	Balance, BalancesCall, Call, Index, Runtime, SignedExtra, SignedPayload, UncheckedExtrinsic, VERSION,
+};
+use crate::chain_spec;
+
+/// A transaction with everything needed to sign it, as printed by `tx build`.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+struct UnsignedTransaction {
+	call: Call,
+	nonce: Index,
+	tip: Balance,
+	spec_version: u32,
+	genesis_hash: H256,
+}
+
+/// The `tx` subcommands.
+#[derive(Debug, StructOpt, Clone)]
+pub enum TxCmd {
+	/// Build an unsigned transfer, to be signed with `tx sign`.
+	#[structopt(name = "build")]
+	Build {
+		/// SS58 address of the recipient.
+		#[structopt(long)]
+		to: String,
+
+		/// Amount to transfer, in the smallest unit.
+		#[structopt(long)]
+		amount: Balance,
+
+		/// Nonce of the sending account, as reported by `system_accountNextIndex`.
+		#[structopt(long)]
+		nonce: Index,
+
+		/// Hash of the chain's genesis block, as reported by `chain_getBlockHash 0`.
+		#[structopt(long)]
+		genesis_hash: String,
+
+		/// Tip for the block author, in the smallest unit.
+		#[structopt(long, default_value = "0")]
+		tip: Balance,
+
+		/// Spec version of the runtime the transaction will be included under.
+		/// Defaults to the runtime this node was built with.
+		#[structopt(long)]
+		spec_version: Option<u32>,
+	},
+
+	/// Sign a transaction built with `tx build`.
+	#[structopt(name = "sign")]
+	Sign {
+		/// The sender's secret phrase, seed or URI.
+		#[structopt(long)]
+		suri: String,
+
+		/// The hex encoded unsigned transaction.
+		transaction: String,
+	},
+}
+
+impl TxCmd {
+	pub fn run(self) -> error::Result<()> {
+		match self {
+			TxCmd::Build { to, amount, nonce, genesis_hash, tip, spec_version } => {
+				let dest = chain_spec::parse_account(&to)?;
+				let transaction = UnsignedTransaction {
+					call: Call::Balances(BalancesCall::transfer(dest.into(), amount)),
+					nonce,
+					tip,
+					spec_version: spec_version.unwrap_or(VERSION.spec_version),
+					genesis_hash: parse_hash(&genesis_hash)?,
+				};
+
+				println!("{}", to_hex(&transaction.encode(), false));
+				Ok(())
+			}
+			TxCmd::Sign { suri, transaction } => {
+				let bytes = from_hex(&transaction)
+					.map_err(|e| format!("Transaction is not valid hex: {:?}", e))?;
+				let transaction = UnsignedTransaction::decode(&mut &bytes[..])
+					.map_err(|e| format!("Invalid transaction: {:?}", e))?;
+				let pair = sr25519::Pair::from_string(&suri, None)
+					.map_err(|e| format!("Invalid secret {:?}", e))?;
+
+				println!("{}", to_hex(&sign(transaction, &pair).encode(), false));
+				Ok(())
+			}
+		}
+	}
+}
+
+fn parse_hash(hash: &str) -> Result<H256, String> {
+	let bytes = from_hex(hash).map_err(|e| format!("Invalid hash {}: {:?}", hash, e))?;
+	if bytes.len() != 32 {
+		return Err(format!("Invalid hash {}: expected 32 bytes", hash))
+	}
+
+	Ok(H256::from_slice(&bytes))
+}
+
+/// Sign `transaction` as an immortal transaction from `pair`'s account.
+fn sign(transaction: UnsignedTransaction, pair: &sr25519::Pair) -> UncheckedExtrinsic {
+	let extra: SignedExtra = (
+		system::CheckVersion::<Runtime>::new(),
+		system::CheckGenesis::<Runtime>::new(),
+		system::CheckEra::<Runtime>::from(Era::Immortal),
+		system::CheckNonce::<Runtime>::from(transaction.nonce),
+		system::CheckWeight::<Runtime>::new(),
+		transaction_payment::ChargeTransactionPayment::<Runtime>::from(transaction.tip),
+	);
+	// The data each extension signs without including it in the extrinsic.
+	// Immortal transactions sign the genesis hash in place of an era's block hash.
+	let additional_signed = (
+		transaction.spec_version,
+		transaction.genesis_hash,
+		transaction.genesis_hash,
+		(),
+		(),
+		(),
+	);
+
+	let payload = SignedPayload::from_raw(transaction.call, extra, additional_signed);
+	let signature = payload.using_encoded(|payload| pair.sign(payload));
+	let (call, extra, _) = payload.deconstruct();
+	let signer = MultiSigner::from(pair.public()).into_account();
+
+	UncheckedExtrinsic::new_signed(call, signer.into(), signature.into(), extra)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::crypto::AccountId32;
+	use sp_runtime::traits::Checkable;
+
+	/// State holding a genesis block hash, as `CheckGenesis` and `CheckEra` read it.
+	fn new_test_ext() -> sp_io::TestExternalities {
+		system::GenesisConfig::default().build_storage::<Runtime>().unwrap().into()
+	}
+
+	fn transfer(genesis_hash: H256, spec_version: u32) -> UnsignedTransaction {
+		UnsignedTransaction {
+			call: Call::Balances(BalancesCall::transfer(AccountId32::new([1; 32]).into(), 1000)),
+			nonce: 3,
+			tip: 10,
+			spec_version,
+			genesis_hash,
+		}
+	}
+
+	#[test]
+	fn signed_transactions_pass_the_runtime_checks() {
+		new_test_ext().execute_with(|| {
+			let pair = sr25519::Pair::from_seed(&[7; 32]);
+			let genesis_hash = system::Module::<Runtime>::block_hash(0);
+			let transaction = transfer(genesis_hash, VERSION.spec_version);
+			let call = transaction.call.clone();
+
+			// Through the hex encoding `tx build` prints and `tx sign` reads
+			let transaction = UnsignedTransaction::decode(&mut &transaction.encode()[..]).unwrap();
+			let xt = UncheckedExtrinsic::decode(&mut &sign(transaction, &pair).encode()[..]).unwrap();
+
+			let checked = xt.check(&system::ChainContext::<Runtime>::default()).unwrap();
+			let signer = MultiSigner::from(pair.public()).into_account();
+			assert_eq!(checked.signed.map(|(account, _)| account), Some(signer));
+			assert_eq!(checked.function, call);
+		});
+	}
+
+	#[test]
+	fn transactions_for_another_chain_or_runtime_fail_the_runtime_checks() {
+		new_test_ext().execute_with(|| {
+			let pair = sr25519::Pair::from_seed(&[7; 32]);
+			let genesis_hash = system::Module::<Runtime>::block_hash(0);
+
+			let other_chain = sign(transfer(H256::repeat_byte(1), VERSION.spec_version), &pair);
+			assert!(other_chain.check(&system::ChainContext::<Runtime>::default()).is_err());
+
+			let other_runtime = sign(transfer(genesis_hash, VERSION.spec_version + 1), &pair);
+			assert!(other_runtime.check(&system::ChainContext::<Runtime>::default()).is_err());
+		});
+	}
+}